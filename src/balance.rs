@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+use solana_sdk::native_token::lamports_to_sol;
+
+const RECENT_SPEND_WINDOW: usize = 20;
+pub const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Fires once when the wallet balance first drops below `floor`, and re-arms
+/// once the balance is back above it.
+pub struct BalanceWatch {
+    floor: u64,
+    alerted: bool,
+    recent_spend: VecDeque<u64>,
+}
+
+impl BalanceWatch {
+    pub fn new(floor: u64) -> Self {
+        Self {
+            floor,
+            alerted: false,
+            recent_spend: VecDeque::with_capacity(RECENT_SPEND_WINDOW),
+        }
+    }
+
+    pub fn record_spend(&mut self, lamports: u64) {
+        if self.recent_spend.len() == RECENT_SPEND_WINDOW {
+            self.recent_spend.pop_front();
+        }
+        self.recent_spend.push_back(lamports);
+    }
+
+    /// Rounds the balance can still pay for at the recent average spend.
+    pub fn runway_rounds(&self, balance: u64) -> Option<u64> {
+        if self.recent_spend.is_empty() {
+            return None;
+        }
+        let avg = self.recent_spend.iter().sum::<u64>() / self.recent_spend.len() as u64;
+        (avg > 0).then(|| balance / avg)
+    }
+
+    /// Returns the alert message the first time the balance crosses below the floor.
+    pub fn check(&mut self, balance: u64) -> Option<String> {
+        if balance >= self.floor {
+            self.alerted = false;
+            return None;
+        }
+        if self.alerted {
+            return None;
+        }
+        self.alerted = true;
+        let runway = match self.runway_rounds(balance) {
+            Some(rounds) => format!("~{} rounds of runway left", rounds),
+            None => "runway unknown (no deploys yet)".to_string(),
+        };
+        Some(format!(
+            "wallet balance {} SOL dropped below {} SOL, {}",
+            lamports_to_sol(balance),
+            lamports_to_sol(self.floor),
+            runway
+        ))
+    }
+}
+
+/// Lamports spent over the last 24 hours of wall-clock time, against a cap.
+/// Entries carry wall-clock timestamps so they survive a restart through the
+/// state file.
+pub struct DailySpend {
+    cap: u64,
+    spends: VecDeque<(SystemTime, u64)>,
+}
+
+impl DailySpend {
+    pub fn new(cap: u64) -> Self {
+        Self {
+            cap,
+            spends: VecDeque::new(),
+        }
+    }
+
+    /// Picks up the spends saved by an earlier run.
+    pub fn restore(&mut self, spends: &[(SystemTime, u64)]) {
+        self.spends.extend(spends);
+    }
+
+    pub fn spends(&self) -> Vec<(SystemTime, u64)> {
+        self.spends.iter().copied().collect()
+    }
+
+    pub fn record(&mut self, lamports: u64) {
+        self.record_at(SystemTime::now(), lamports);
+    }
+
+    fn record_at(&mut self, at: SystemTime, lamports: u64) {
+        self.spends.push_back((at, lamports));
+    }
+
+    pub fn remaining(&mut self) -> u64 {
+        self.remaining_at(SystemTime::now())
+    }
+
+    fn remaining_at(&mut self, now: SystemTime) -> u64 {
+        // a clock stepping backwards keeps the entry rather than dropping it early
+        self.spends.retain(|(at, _)| now.duration_since(*at).unwrap_or_default() < DAY);
+        let spent = self.spends.iter().map(|(_, lamports)| lamports).sum::<u64>();
+        self.cap.saturating_sub(spent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daily_spend_forgets_spends_older_than_a_day() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut daily = DailySpend::new(1_000);
+        daily.record_at(start, 300);
+        daily.record_at(start + Duration::from_secs(3600), 200);
+        assert_eq!(daily.remaining_at(start + Duration::from_secs(7200)), 500);
+        assert_eq!(daily.remaining_at(start + DAY), 800);
+        assert_eq!(daily.remaining_at(start + DAY + Duration::from_secs(3600)), 1_000);
+    }
+
+    #[test]
+    fn daily_spend_restores_saved_spends() {
+        let now = SystemTime::now();
+        let mut daily = DailySpend::new(1_000);
+        daily.restore(&[(now - 2 * DAY, 400), (now - Duration::from_secs(60), 250)]);
+        assert_eq!(daily.remaining_at(now), 750);
+        assert_eq!(daily.spends(), vec![(now - Duration::from_secs(60), 250)]);
+    }
+
+    #[test]
+    fn daily_spend_never_goes_below_zero() {
+        let now = SystemTime::now();
+        let mut daily = DailySpend::new(100);
+        daily.record_at(now, 150);
+        assert_eq!(daily.remaining_at(now), 0);
+    }
+
+    #[test]
+    fn balance_watch_alerts_once_per_crossing() {
+        let mut watch = BalanceWatch::new(1_000);
+        watch.record_spend(100);
+        watch.record_spend(300);
+        let alert = watch.check(800).unwrap();
+        assert!(alert.contains("~4 rounds of runway left"), "{}", alert);
+        assert_eq!(watch.check(700), None);
+        assert_eq!(watch.check(1_200), None);
+        assert!(watch.check(900).is_some());
+    }
+}
@@ -0,0 +1,126 @@
+use ore_api::prelude::Round;
+use solana_sdk::native_token::lamports_to_sol;
+
+/// Share of the losing squares' SOL that goes to the treasury instead of the winners.
+const VAULT_RATE: f64 = 0.1;
+/// ORE protocol fee plus the refined program fee, charged on every deploy.
+const DEPLOY_FEE_RATE: f64 = 0.01 + 0.005;
+/// ORE minted to the winning square each round, motherlode not included.
+const ORE_PER_ROUND: f64 = 1.0;
+
+#[derive(Debug)]
+pub struct SquareBreakEven {
+    pub index: usize,
+    pub deployed: u64,
+    /// Largest deploy with non-negative expected value, in lamports.
+    pub max_amount: u64,
+    /// Deploy that maximizes the expected value, in lamports.
+    pub best_amount: u64,
+    /// Expected profit of `best_amount`, in lamports.
+    pub best_ev: f64,
+}
+
+/// Expected value per square of adding `x` lamports: with probability 1/25
+/// the square wins and `x` takes its share `x / (d + x)` of the losing SOL
+/// (after the vault cut) and of the round's ORE, otherwise `x` is lost; the
+/// deploy fee is paid either way.
+///
+///   EV(x) = x * (W / (25 (d + x)) - c),  c = 24/25 + fee
+///
+/// so EV is positive for `x < W / (25 c) - d` and peaks at
+/// `x = sqrt(W d / (25 c)) - d`.
+pub fn break_even(round: &Round, ore_price: f64, sol_price: f64) -> Vec<SquareBreakEven> {
+    let ore_reward = ORE_PER_ROUND * ore_price / sol_price * 1e9;
+    let cost = 24.0 / 25.0 + DEPLOY_FEE_RATE;
+    (0..25)
+        .map(|index| {
+            let d = round.deployed[index] as f64;
+            let losing = round.total_deployed.saturating_sub(round.deployed[index]) as f64;
+            let w = losing * (1.0 - VAULT_RATE) + ore_reward;
+            let max_amount = (w / (25.0 * cost) - d).max(0.0);
+            let best_amount = ((w * d / (25.0 * cost)).sqrt() - d).clamp(0.0, max_amount);
+            let best_ev = if d + best_amount > 0.0 {
+                best_amount * (w / (25.0 * (d + best_amount)) - cost)
+            } else {
+                0.0
+            };
+            SquareBreakEven {
+                index,
+                deployed: round.deployed[index],
+                max_amount: max_amount as u64,
+                best_amount: best_amount as u64,
+                best_ev,
+            }
+        })
+        .collect()
+}
+
+pub fn print_break_even(round: &Round, ore_price: f64, sol_price: f64) {
+    println!(
+        "round {}: {} SOL deployed, ORE {} USDC, SOL {} USDC",
+        round.id,
+        lamports_to_sol(round.total_deployed),
+        ore_price,
+        sol_price
+    );
+    println!("square  deployed SOL  max +EV SOL  best SOL  best EV SOL");
+    for square in break_even(round, ore_price, sol_price) {
+        println!(
+            "{:>6}  {:>12.4}  {:>11.4}  {:>8.4}  {:>11.6}",
+            square.index,
+            lamports_to_sol(square.deployed),
+            lamports_to_sol(square.max_amount),
+            lamports_to_sol(square.best_amount),
+            square.best_ev / 1e9
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steel::Zeroable;
+
+    const SOL: u64 = 1_000_000_000;
+
+    /// 1 SOL on square 0, 99 SOL on square 2, nothing elsewhere. With ORE
+    /// priced at one SOL the round's ORE is worth 1 SOL and c = 0.975.
+    fn round() -> Round {
+        let mut round = Round::zeroed();
+        round.deployed[0] = SOL;
+        round.deployed[2] = 99 * SOL;
+        round.total_deployed = 100 * SOL;
+        round
+    }
+
+    #[test]
+    fn occupied_square_matches_the_hand_computed_values() {
+        let square = &break_even(&round(), 187.0, 187.0)[0];
+        // W = 99 * 0.9 + 1 = 90.1 SOL
+        // max = W / (25 c) - d = 90.1 / 24.375 - 1 = 2.696410256 SOL
+        // best = sqrt(W d / (25 c)) - d = sqrt(3.696410256) - 1 = 0.922605070 SOL
+        // EV(best) = best * (W / (25 (d + best)) - c) = 0.829920113 SOL
+        assert_eq!(square.deployed, SOL);
+        assert_eq!(square.max_amount, 2_696_410_256);
+        assert_eq!(square.best_amount, 922_605_070);
+        assert!((square.best_ev - 829_920_112.88).abs() < 1.0, "{}", square.best_ev);
+    }
+
+    #[test]
+    fn empty_square_has_room_but_no_best_amount() {
+        let square = &break_even(&round(), 187.0, 187.0)[1];
+        // W = 100 * 0.9 + 1 = 91 SOL, max = 91 / 24.375 = 3.733333333 SOL,
+        // with d = 0 the optimum sqrt(W d / (25 c)) - d degenerates to 0
+        assert_eq!(square.max_amount, 3_733_333_333);
+        assert_eq!(square.best_amount, 0);
+        assert_eq!(square.best_ev, 0.0);
+    }
+
+    #[test]
+    fn crowded_square_is_never_worth_it() {
+        let square = &break_even(&round(), 187.0, 187.0)[2];
+        // W = 0.9 + 1 = 1.9 SOL is far below what the 99 SOL already there need
+        assert_eq!((square.max_amount, square.best_amount), (0, 0));
+        assert!(square.best_ev <= 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+/// Expands `--config <PATH>` into command line flags. The file is a JSON
+/// object keyed by flag name (`per_round_deploy_amount` or
+/// `per-round-deploy-amount`); its flags are placed before the ones given on
+/// the command line, which therefore win when both set the same value.
+/// `false` and `null` leave a flag at its default.
+pub fn args_with_config(args: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+    let config: serde_json::Map<String, Value> = serde_json::from_slice(&std::fs::read(&path)?)?;
+
+    let mut from_file = vec![];
+    for (key, value) in &config {
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Bool(true) => from_file.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            Value::Array(items) => {
+                let items = items.iter().map(scalar).collect::<anyhow::Result<Vec<_>>>()?;
+                from_file.push(format!("{}={}", flag, items.join(",")));
+            }
+            value => from_file.push(format!("{}={}", flag, scalar(value)?)),
+        }
+    }
+    let mut args = args.into_iter();
+    Ok(args.next().into_iter().chain(from_file).chain(args).collect())
+}
+
+fn config_path(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+fn scalar(value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => anyhow::bail!("unsupported config value: {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn config_path_accepts_both_forms() {
+        assert_eq!(config_path(&strings(&["bot", "--config", "a.json"])), Some("a.json".to_string()));
+        assert_eq!(config_path(&strings(&["bot", "--config=b.json"])), Some("b.json".to_string()));
+        assert_eq!(config_path(&strings(&["bot", "--rpc", "x"])), None);
+    }
+
+    #[test]
+    fn without_config_args_pass_through() {
+        let args = strings(&["bot", "--rpc", "x"]);
+        assert_eq!(args_with_config(args.clone()).unwrap(), args);
+    }
+
+    #[test]
+    fn file_flags_go_before_command_line_flags() {
+        let path = std::env::temp_dir().join(format!("ore-config-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"dry-run": false, "explain": true, "per_round_deploy_amount": 0.5, "squares": [1, 2], "webhook_url": null}"#,
+        )
+        .unwrap();
+        let config = format!("--config={}", path.display());
+
+        let args = args_with_config(strings(&["bot", &config, "--per-round-deploy-amount", "0.1"])).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            args,
+            strings(&[
+                "bot",
+                "--explain",
+                "--per-round-deploy-amount=0.5",
+                "--squares=1,2",
+                &config,
+                "--per-round-deploy-amount",
+                "0.1",
+            ])
+        );
+    }
+
+    #[test]
+    fn nested_values_are_rejected() {
+        assert!(scalar(&serde_json::json!({"a": 1})).is_err());
+        assert_eq!(scalar(&serde_json::json!("x")).unwrap(), "x");
+        assert_eq!(scalar(&serde_json::json!(3)).unwrap(), "3");
+    }
+}
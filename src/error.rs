@@ -0,0 +1,84 @@
+use std::fmt;
+use solana_sdk::pubkey::Pubkey;
+use steel::{AccountDeserialize, Discriminator};
+
+#[derive(Debug)]
+pub enum OreError {
+    /// Account data that does not decode as the expected ORE account.
+    Deserialize {
+        account: Pubkey,
+        type_name: &'static str,
+        expected_discriminator: u8,
+        actual_discriminator: Option<u8>,
+        expected_len: usize,
+        actual_len: usize,
+    },
+    /// The rpc answered getProgramAccounts with 410 Gone, it doesn't serve the method.
+    ProgramAccountsUnsupported,
+}
+
+impl OreError {
+    /// Empty or short data with the right discriminator usually is a bad rpc
+    /// read, anything else points at a program layout change.
+    pub fn is_truncated(&self) -> bool {
+        match self {
+            OreError::Deserialize {
+                expected_discriminator,
+                actual_discriminator,
+                expected_len,
+                actual_len,
+                ..
+            } => {
+                *actual_len == 0
+                    || (*actual_len < *expected_len && *actual_discriminator == Some(*expected_discriminator))
+            }
+            OreError::ProgramAccountsUnsupported => false,
+        }
+    }
+}
+
+impl fmt::Display for OreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OreError::Deserialize {
+                account,
+                type_name,
+                expected_discriminator,
+                actual_discriminator,
+                expected_len,
+                actual_len,
+            } => write!(
+                f,
+                "{} {} does not decode ({}): discriminator {:?} (expected {}), {} bytes (expected {})",
+                type_name,
+                account,
+                if self.is_truncated() { "truncated or empty read" } else { "layout mismatch" },
+                actual_discriminator,
+                expected_discriminator,
+                actual_len,
+                expected_len
+            ),
+            OreError::ProgramAccountsUnsupported => write!(
+                f,
+                "the rpc provider does not support getProgramAccounts, use a different rpc for this command"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OreError {}
+
+/// `try_from_bytes` with an `OreError::Deserialize` describing what was read.
+pub fn deserialize_account<T>(account: &Pubkey, data: &[u8]) -> Result<T, OreError>
+where
+    T: AccountDeserialize + Discriminator + Copy,
+{
+    T::try_from_bytes(data).copied().map_err(|_| OreError::Deserialize {
+        account: *account,
+        type_name: std::any::type_name::<T>().rsplit("::").next().unwrap_or_default(),
+        expected_discriminator: T::discriminator(),
+        actual_discriminator: data.first().copied(),
+        expected_len: 8 + std::mem::size_of::<T>(),
+        actual_len: data.len(),
+    })
+}
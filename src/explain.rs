@@ -0,0 +1,136 @@
+use std::fmt;
+use serde::Serialize;
+use solana_sdk::native_token::lamports_to_sol;
+
+use crate::grid::BoardGrid;
+
+#[derive(Debug, Serialize)]
+pub struct Guard {
+    pub name: &'static str,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SquareExplain {
+    pub index: usize,
+    pub row: usize,
+    pub col: usize,
+    pub round_deployed: u64,
+    pub deploy: u64,
+}
+
+/// Full reasoning behind one deploy, emitted by `--explain`.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub round_id: u64,
+    pub slot: u64,
+    pub slot_left: u64,
+    pub ore_price: f64,
+    pub sol_price: f64,
+    pub ore_refined_rate: f64,
+    pub deploy_amount: u64,
+    pub pot: u64,
+    pub pot_ema: Option<f64>,
+    pub guards: Vec<Guard>,
+    /// Squares the refined program picks, taken from simulating the deploy.
+    pub squares: Vec<SquareExplain>,
+}
+
+impl Explanation {
+    pub fn squares_from(round_deployed: &[u64; 25], deployed: &[u64; 25]) -> Vec<SquareExplain> {
+        (0..25)
+            .filter(|i| deployed[*i] > 0)
+            .map(|index| {
+                let (row, col) = BoardGrid::row_col(index);
+                SquareExplain {
+                    index,
+                    row,
+                    col,
+                    round_deployed: round_deployed[index],
+                    deploy: deployed[index],
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "round {} at slot {} ({} slots left)", self.round_id, self.slot, self.slot_left)?;
+        writeln!(
+            f,
+            "  inputs: ORE {} USDC, SOL {} USDC, ore_refined_rate {}, amount {} SOL",
+            self.ore_price, self.sol_price, self.ore_refined_rate, lamports_to_sol(self.deploy_amount)
+        )?;
+        writeln!(
+            f,
+            "  pot: {} SOL (recent average: {})",
+            lamports_to_sol(self.pot),
+            match self.pot_ema {
+                Some(ema) => format!("{:.4} SOL", ema / 1e9),
+                None => "n/a".to_string(),
+            }
+        )?;
+        writeln!(f, "  guards passed:")?;
+        for guard in &self.guards {
+            writeln!(f, "    {}: {}", guard.name, guard.detail)?;
+        }
+        writeln!(f, "  squares chosen by the refined program:")?;
+        for square in &self.squares {
+            writeln!(
+                f,
+                "    #{:<2} (row {}, col {}): {} SOL already deployed, deploying {} SOL",
+                square.index,
+                square.row,
+                square.col,
+                lamports_to_sol(square.round_deployed),
+                lamports_to_sol(square.deploy)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squares_from_keeps_deployed_squares_only() {
+        let mut round_deployed = [0; 25];
+        round_deployed[7] = 3_000_000_000;
+        let mut deployed = [0; 25];
+        deployed[7] = 100_000_000;
+        deployed[24] = 200_000_000;
+
+        let squares = Explanation::squares_from(&round_deployed, &deployed);
+        assert_eq!(squares.len(), 2);
+        assert_eq!((squares[0].index, squares[0].row, squares[0].col), (7, 1, 2));
+        assert_eq!((squares[0].round_deployed, squares[0].deploy), (3_000_000_000, 100_000_000));
+        assert_eq!((squares[1].index, squares[1].row, squares[1].col), (24, 4, 4));
+    }
+
+    #[test]
+    fn display_lists_guards_and_squares() {
+        let mut deployed = [0; 25];
+        deployed[7] = 100_000_000;
+        let explanation = Explanation {
+            round_id: 42,
+            slot: 1_000,
+            slot_left: 5,
+            ore_price: 2.0,
+            sol_price: 150.0,
+            ore_refined_rate: 0.5,
+            deploy_amount: 100_000_000,
+            pot: 2_000_000_000,
+            pot_ema: None,
+            guards: vec![Guard { name: "window", detail: "slot 1000 <= 1003".to_string() }],
+            squares: Explanation::squares_from(&[0; 25], &deployed),
+        };
+        let rendered = explanation.to_string();
+        assert!(rendered.starts_with("round 42 at slot 1000 (5 slots left)\n"));
+        assert!(rendered.contains("  pot: 2 SOL (recent average: n/a)\n"));
+        assert!(rendered.contains("    window: slot 1000 <= 1003\n"));
+        assert!(rendered.contains("    #7  (row 1, col 2): 0 SOL already deployed, deploying 0.1 SOL\n"));
+    }
+}
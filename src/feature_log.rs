@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use ore_api::prelude::Round;
+
+const RECENT_WINNERS: usize = 10;
+
+/// Features observed for a round at decision time.
+#[derive(Debug, Clone, Copy)]
+pub struct Features {
+    pub round_id: u64,
+    pub slot: u64,
+    pub slot_left: u64,
+    pub ore_price: f64,
+    pub sol_price: f64,
+    pub total_deployed: u64,
+    pub deployed: [u64; 25],
+}
+
+/// Writes one CSV row per round: the features seen at decision time plus the
+/// winning square once the round is over.
+pub struct FeatureLog {
+    file: File,
+    recent_winners: VecDeque<usize>,
+}
+
+impl FeatureLog {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            let squares = (0..25).map(|i| format!("d{}", i)).collect::<Vec<_>>().join(",");
+            writeln!(
+                file,
+                "round_id,slot,slot_left,ore_price,sol_price,total_deployed,{},recent_winning_squares,winning_square",
+                squares
+            )?;
+        }
+        Ok(Self {
+            file,
+            recent_winners: VecDeque::with_capacity(RECENT_WINNERS),
+        })
+    }
+
+    /// Writes the row for `features` once `round` (the same round, finished) is known.
+    pub fn write(&mut self, features: &Features, round: &Round) -> anyhow::Result<()> {
+        let winning_square = round.rng().map(|rng| round.winning_square(rng));
+        let deployed = features.deployed.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+        let recent = self.recent_winners.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(";");
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{},{},{},{}",
+            features.round_id,
+            features.slot,
+            features.slot_left,
+            features.ore_price,
+            features.sol_price,
+            features.total_deployed,
+            deployed,
+            recent,
+            winning_square.map(|s| s.to_string()).unwrap_or_default()
+        )?;
+
+        if let Some(square) = winning_square {
+            if self.recent_winners.len() == RECENT_WINNERS {
+                self.recent_winners.pop_front();
+            }
+            self.recent_winners.push_back(square);
+        }
+        Ok(())
+    }
+}
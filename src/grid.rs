@@ -0,0 +1,102 @@
+use std::fmt;
+use solana_sdk::native_token::lamports_to_sol;
+
+pub const GRID_SIZE: usize = 5;
+
+/// The 25 squares of a round laid out as the 5x5 board, index = row * 5 + col.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardGrid(pub [u64; GRID_SIZE * GRID_SIZE]);
+
+impl BoardGrid {
+    pub fn row_col(index: usize) -> (usize, usize) {
+        (index / GRID_SIZE, index % GRID_SIZE)
+    }
+
+    pub fn index(row: usize, col: usize) -> usize {
+        row * GRID_SIZE + col
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> u64 {
+        self.0[Self::index(row, col)]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u64]> {
+        self.0.chunks(GRID_SIZE)
+    }
+
+    pub fn cols(&self) -> impl Iterator<Item = [u64; GRID_SIZE]> + '_ {
+        (0..GRID_SIZE).map(|col| std::array::from_fn(|row| self.get(row, col)))
+    }
+
+    /// Indices of the (up to 8) squares touching `index`, diagonals included.
+    pub fn neighbors(index: usize) -> Vec<usize> {
+        let (row, col) = Self::row_col(index);
+        let mut neighbors = vec![];
+        for r in row.saturating_sub(1)..=(row + 1).min(GRID_SIZE - 1) {
+            for c in col.saturating_sub(1)..=(col + 1).min(GRID_SIZE - 1) {
+                if (r, c) != (row, col) {
+                    neighbors.push(Self::index(r, c));
+                }
+            }
+        }
+        neighbors
+    }
+}
+
+impl From<[u64; GRID_SIZE * GRID_SIZE]> for BoardGrid {
+    fn from(deployed: [u64; GRID_SIZE * GRID_SIZE]) -> Self {
+        Self(deployed)
+    }
+}
+
+impl fmt::Display for BoardGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            for amount in row {
+                write!(f, "{:>10.4}", lamports_to_sol(*amount))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_and_row_col_roundtrip() {
+        for index in 0..GRID_SIZE * GRID_SIZE {
+            let (row, col) = BoardGrid::row_col(index);
+            assert_eq!(BoardGrid::index(row, col), index);
+        }
+        assert_eq!(BoardGrid::row_col(7), (1, 2));
+    }
+
+    #[test]
+    fn neighbors_stay_on_the_board() {
+        assert_eq!(BoardGrid::neighbors(0), vec![1, 5, 6]);
+        assert_eq!(BoardGrid::neighbors(24), vec![18, 19, 23]);
+        assert_eq!(BoardGrid::neighbors(2), vec![1, 3, 6, 7, 8]);
+        assert_eq!(BoardGrid::neighbors(12), vec![6, 7, 8, 11, 13, 16, 17, 18]);
+    }
+
+    #[test]
+    fn rows_and_cols() {
+        let grid = BoardGrid::from(std::array::from_fn(|i| i as u64));
+        assert_eq!(grid.rows().nth(1).unwrap(), &[5, 6, 7, 8, 9]);
+        assert_eq!(grid.cols().nth(2).unwrap(), [2, 7, 12, 17, 22]);
+        assert_eq!(grid.get(4, 0), 20);
+    }
+
+    #[test]
+    fn display_prints_one_line_per_row() {
+        let mut deployed = [0; 25];
+        deployed[6] = 1_500_000_000;
+        let rendered = BoardGrid(deployed).to_string();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), GRID_SIZE);
+        assert_eq!(lines[1], format!("{:>10.4}{:>10.4}{:>10.4}{:>10.4}{:>10.4}", 0.0, 1.5, 0.0, 0.0, 0.0));
+    }
+}
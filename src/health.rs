@@ -0,0 +1,112 @@
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Price,
+    Rpc,
+    Jito,
+    Data,
+    Poll,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [Subsystem::Price, Subsystem::Rpc, Subsystem::Jito, Subsystem::Data, Subsystem::Poll];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::Price => "price",
+            Subsystem::Rpc => "rpc",
+            Subsystem::Jito => "jito",
+            Subsystem::Data => "data",
+            Subsystem::Poll => "poll",
+        }
+    }
+
+    fn degraded_behavior(&self) -> &'static str {
+        match self {
+            Subsystem::Price => "price stale -> the last cached price is passed to the refined instruction",
+            Subsystem::Rpc => "rpc failing -> deploy attempts are skipped until it recovers",
+            Subsystem::Jito => "jito down -> deploys rely on rpc submission only",
+            Subsystem::Data => "board/clock stale -> slot timing may be off",
+            Subsystem::Poll => "account polling failing -> deploys are paused until it recovers",
+        }
+    }
+}
+
+/// Aggregated status of the subsystems the mining loop depends on.
+#[derive(Debug, Default)]
+pub struct HealthState {
+    degraded: [bool; 5],
+}
+
+impl HealthState {
+    /// Records the latest status of a subsystem and logs a single summary
+    /// whenever the overall picture changes. Returns whether it changed.
+    pub fn update(&mut self, subsystem: Subsystem, ok: bool) -> bool {
+        let slot = &mut self.degraded[subsystem as usize];
+        if *slot == !ok {
+            return false;
+        }
+        *slot = !ok;
+        self.log_summary();
+        true
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        !self.degraded.iter().any(|d| *d)
+    }
+
+    pub fn is_degraded(&self, subsystem: Subsystem) -> bool {
+        self.degraded[subsystem as usize]
+    }
+
+    pub fn degraded(&self) -> impl Iterator<Item = Subsystem> + '_ {
+        Subsystem::ALL.into_iter().filter(|s| self.is_degraded(*s))
+    }
+
+    fn log_summary(&self) {
+        if self.is_healthy() {
+            info!("health: all subsystems ok");
+            return;
+        }
+        let summary = self
+            .degraded()
+            .map(|s| s.degraded_behavior())
+            .collect::<Vec<_>>()
+            .join("; ");
+        warn!("health: degraded: {}", summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_reports_only_changes() {
+        let mut health = HealthState::default();
+        assert!(!health.update(Subsystem::Rpc, true));
+        assert!(health.update(Subsystem::Rpc, false));
+        assert!(!health.update(Subsystem::Rpc, false));
+        assert!(health.update(Subsystem::Rpc, true));
+        assert!(health.is_healthy());
+    }
+
+    #[test]
+    fn degraded_lists_failing_subsystems_in_order() {
+        let mut health = HealthState::default();
+        health.update(Subsystem::Poll, false);
+        health.update(Subsystem::Price, false);
+        assert!(!health.is_healthy());
+        assert!(health.is_degraded(Subsystem::Price));
+        assert!(!health.is_degraded(Subsystem::Jito));
+        assert_eq!(health.degraded().collect::<Vec<_>>(), vec![Subsystem::Price, Subsystem::Poll]);
+    }
+
+    #[test]
+    fn all_matches_the_discriminants() {
+        for (index, subsystem) in Subsystem::ALL.iter().enumerate() {
+            assert_eq!(*subsystem as usize, index);
+        }
+    }
+}
@@ -1,7 +1,19 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use ore_api::prelude::{Round, TOKEN_DECIMALS};
 use serde::{Deserialize, Serialize};
 use solana_client::client_error::reqwest;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::native_token::lamports_to_sol;
 use solana_sdk::pubkey::Pubkey;
-use tracing::info;
+use spl_token::amount_to_ui_amount;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::error::deserialize_account;
+use crate::grid::GRID_SIZE;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BoardHistory {
@@ -19,7 +31,8 @@ pub struct BoardHistory {
     pub ts: u64,
 }
 
-pub async fn get_history_winners() -> anyhow::Result<Vec<Pubkey>> {
+/// The board history served by the ore-bsm indexer, most recent round first.
+pub async fn get_board_history() -> anyhow::Result<Vec<BoardHistory>> {
     let resp = reqwest::get("https://ore-bsm.onrender.com/board/history")
         .await?
         .text()
@@ -27,14 +40,144 @@ pub async fn get_history_winners() -> anyhow::Result<Vec<Pubkey>> {
 
     let arr: Vec<Vec<serde_json::Value>> = serde_json::from_str(&resp)?;
 
-    let mut winners = vec![];
+    let mut history = vec![];
     for item in arr {
-        let key_bytes: Vec<u8> = serde_json::from_value(item[0].clone())?;
-        let board_info: BoardHistory = serde_json::from_value(item[1].clone())?;
-        let pubkey = Pubkey::try_from(board_info.top_miner.as_slice()).unwrap();
+        let Some(board_info) = item.get(1) else {
+            warn!("board history entry without round data, skipping");
+            continue;
+        };
+        history.push(serde_json::from_value::<BoardHistory>(board_info.clone())?);
+    }
+    history.sort_by(|a, b| b.round_id.cmp(&a.round_id));
+    Ok(history)
+}
+
+impl BoardHistory {
+    /// The round's top miner, `None` if the indexer sent bytes that aren't a pubkey.
+    pub fn top_miner(&self) -> Option<Pubkey> {
+        Pubkey::try_from(self.top_miner.as_slice()).ok()
+    }
+}
+
+pub async fn get_history_winners() -> anyhow::Result<Vec<Pubkey>> {
+    let mut winners = vec![];
+    for board_info in get_board_history().await? {
+        let Some(pubkey) = board_info.top_miner() else {
+            warn!("board id: {} has an invalid top miner, skipping", board_info.round_id);
+            continue;
+        };
         info!("board id: {}, winning square: {}, top miner: {}", board_info.round_id, board_info.winning_square, pubkey);
         winners.push(pubkey);
     }
 
     Ok(winners)
 }
+
+/// Prints how often each square won over the last `rounds` rounds of the
+/// board history, as a 5x5 heatmap, plus averages over the same rounds.
+pub async fn print_history(rounds: usize) -> anyhow::Result<()> {
+    let history = get_board_history().await?;
+    if history.len() < rounds {
+        warn!("history only has {} rounds, {} requested", history.len(), rounds);
+    }
+    let history = &history[..rounds.min(history.len())];
+    if history.is_empty() {
+        anyhow::bail!("board history is empty");
+    }
+
+    let mut wins = [0usize; GRID_SIZE * GRID_SIZE];
+    for board_info in history {
+        match wins.get_mut(board_info.winning_square as usize) {
+            Some(count) => *count += 1,
+            None => warn!("board id: {} has winning square {} off the board", board_info.round_id, board_info.winning_square),
+        }
+    }
+
+    let n = history.len() as f64;
+    println!(
+        "rounds {}..={} ({} rounds)",
+        history[history.len() - 1].round_id,
+        history[0].round_id,
+        history.len()
+    );
+    println!("winning square frequency:");
+    for row in wins.chunks(GRID_SIZE) {
+        let cells = row
+            .iter()
+            .map(|count| format!("{:>4} ({:>4.1}%)", count, *count as f64 / n * 100.0))
+            .collect::<Vec<_>>();
+        println!("  {}", cells.join(" "));
+    }
+    let total_deployed = history.iter().map(|h| h.total_deployed).sum::<u64>();
+    let num_winners = history.iter().map(|h| h.num_winners as u64).sum::<u64>();
+    let total_minted = history.iter().map(|h| h.total_minted).sum::<u64>();
+    println!("avg total deployed: {:.4} SOL", lamports_to_sol(total_deployed) / n);
+    println!("avg winners: {:.1}", num_winners as f64 / n);
+    println!("total minted: {} ORE", amount_to_ui_amount(total_minted, TOKEN_DECIMALS));
+    Ok(())
+}
+
+/// The `count` wallets that were top miner most often in the history.
+pub async fn get_top_history_winners(count: usize) -> anyhow::Result<Vec<Pubkey>> {
+    let mut wins: HashMap<Pubkey, usize> = HashMap::new();
+    for winner in get_history_winners().await? {
+        *wins.entry(winner).or_default() += 1;
+    }
+    let mut wins = wins.into_iter().collect::<Vec<_>>();
+    wins.sort_by(|a, b| b.1.cmp(&a.1));
+    Ok(wins.into_iter().take(count).map(|(pubkey, _)| pubkey).collect())
+}
+
+/// Fetches the on-chain `Round` accounts for `ids` with at most `concurrency`
+/// requests in flight. Finished rounds are cached in `cache_dir` as raw account
+/// data so repeated runs only hit the RPC for rounds not seen before. Rounds
+/// whose account is gone (closed after expiry) come back as `None`.
+pub async fn fetch_rounds(
+    rpc: Arc<RpcClient>,
+    ids: Vec<u64>,
+    concurrency: usize,
+    cache_dir: &str,
+) -> anyhow::Result<Vec<(u64, Option<Round>)>> {
+    std::fs::create_dir_all(cache_dir)?;
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let total = ids.len();
+    let mut tasks = JoinSet::new();
+    for id in ids {
+        let path = Path::new(cache_dir).join(format!("round-{}.bin", id));
+        if let Ok(data) = std::fs::read(&path) {
+            let round = deserialize_account::<Round>(&ore_api::state::round_pda(id).0, &data)?;
+            tasks.spawn(async move { Ok::<_, anyhow::Error>((id, Some(round))) });
+            continue;
+        }
+
+        let rpc = rpc.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            let round_pda = ore_api::state::round_pda(id);
+            let account = rpc
+                .get_account_with_commitment(&round_pda.0, rpc.commitment())
+                .await?
+                .value;
+            let Some(account) = account else {
+                return Ok((id, None));
+            };
+            let round = deserialize_account::<Round>(&round_pda.0, &account.data)?;
+            // only finished rounds are immutable
+            if round.rng().is_some() {
+                std::fs::write(&path, &account.data)?;
+            }
+            Ok((id, Some(round)))
+        });
+    }
+
+    let mut rounds = Vec::with_capacity(total);
+    while let Some(result) = tasks.join_next().await {
+        rounds.push(result??);
+        if rounds.len() % 100 == 0 || rounds.len() == total {
+            info!("fetched {}/{} rounds", rounds.len(), total);
+        }
+    }
+    rounds.sort_by_key(|(id, _)| *id);
+    Ok(rounds)
+}
@@ -0,0 +1,131 @@
+use std::os::unix::fs::FileTypeExt;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixListener;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Updates a reader can fall behind by before it starts missing some.
+const READER_BACKLOG: usize = 64;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IpcEvent {
+    Snapshot {
+        wallet: String,
+        round_id: u64,
+        slot: u64,
+        slots_remaining: u64,
+        total_deployed: u64,
+        deployed: [u64; 25],
+    },
+    Deploy {
+        wallet: String,
+        round_id: u64,
+        slots_remaining: u64,
+        amount: u64,
+        squares: Vec<usize>,
+        signature: String,
+    },
+}
+
+/// Newline-delimited JSON feed of the mining loop for a companion process on
+/// a UNIX socket. Publishing never waits: without a connected reader the
+/// update is dropped, and a reader that can't keep up skips updates.
+#[derive(Clone, Default)]
+pub struct IpcFeed(Option<broadcast::Sender<String>>);
+
+impl IpcFeed {
+    pub fn bind(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self(None));
+        };
+        // a socket file left behind by an earlier run would fail the bind,
+        // anything else at that path is not ours to remove
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => anyhow::bail!("--ipc-socket {} exists and is not a socket", path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        let listener = UnixListener::bind(path)?;
+        info!("ipc feed on {}", path);
+        let (tx, _) = broadcast::channel::<String>(READER_BACKLOG);
+        let feed = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let mut stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        warn!("ipc accept failed: {:?}", err);
+                        continue;
+                    }
+                };
+                let mut rx = feed.subscribe();
+                tokio::spawn(async move {
+                    loop {
+                        let line = match rx.recv().await {
+                            Ok(line) => line,
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => return,
+                        };
+                        if stream.write_all(line.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self(Some(tx)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn publish(&self, event: &IpcEvent) {
+        let Some(tx) = &self.0 else {
+            return;
+        };
+        if tx.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(event) {
+            Ok(mut line) => {
+                line.push('\n');
+                let _ = tx.send(line);
+            }
+            Err(err) => warn!("ipc event not serializable: {:?}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("ore-ipc-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[tokio::test]
+    async fn bind_replaces_a_stale_socket() {
+        let path = temp_path("stale");
+        let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(stale);
+        assert!(IpcFeed::bind(Some(&path)).unwrap().is_enabled());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_refuses_to_remove_a_regular_file() {
+        let path = temp_path("file");
+        std::fs::write(&path, b"keep me").unwrap();
+        assert!(IpcFeed::bind(Some(&path)).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"keep me");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
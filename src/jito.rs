@@ -1,8 +1,9 @@
 use std::{fmt::Formatter, sync::Arc};
+use std::time::{Duration, Instant};
 use bincode::serialize;
+use futures_util::future::join_all;
 use futures_util::stream::StreamExt;
 use rand::Rng;
-use rand::seq::SliceRandom;
 use serde::{de, Deserialize};
 use serde_json::{json, Value};
 use solana_client::client_error::reqwest;
@@ -68,14 +69,107 @@ where
     Ok(response)
 }
 
-pub async fn send_bundle(bundle: Vec<VersionedTransaction>) -> anyhow::Result<()> {
-    let signature = *bundle
-        .first()
-        .expect("empty bundle")
-        .signatures
-        .first()
-        .expect("empty transaction");
+pub const BLOCK_ENGINE_URLS: [&str; 5] = [
+    "https://amsterdam.mainnet.block-engine.jito.wtf/api/v1/bundles",
+    "https://frankfurt.mainnet.block-engine.jito.wtf/api/v1/bundles",
+    "https://ny.mainnet.block-engine.jito.wtf/api/v1/bundles",
+    "https://slc.mainnet.block-engine.jito.wtf/api/v1/bundles",
+    "https://tokyo.mainnet.block-engine.jito.wtf/api/v1/bundles",
+];
+
+const REGION_LATENCY_ALPHA: f64 = 0.3;
+/// Share of submissions sent to a random region so the estimates of the
+/// others don't go stale between probes.
+const REGION_EXPLORATION: f64 = 0.1;
+
+/// Rolling latency estimate per block engine, fed by periodic probes and by
+/// the bundle submissions themselves.
+#[derive(Default)]
+pub struct JitoRegionSelector {
+    latency_ms: std::sync::Mutex<[Option<f64>; BLOCK_ENGINE_URLS.len()]>,
+}
+
+impl JitoRegionSelector {
+    pub fn observe(&self, index: usize, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let mut latency_ms = self.latency_ms.lock().unwrap();
+        latency_ms[index] = Some(match latency_ms[index] {
+            Some(ema) => ema + REGION_LATENCY_ALPHA * (ms - ema),
+            None => ms,
+        });
+    }
+
+    /// Fastest region measured so far and its latency in ms.
+    pub fn best(&self) -> Option<(usize, f64)> {
+        self.latency_ms
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, ms)| ms.map(|ms| (index, ms)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub fn pick(&self) -> usize {
+        let mut rng = rand::thread_rng();
+        match self.best() {
+            Some((index, _)) if !rng.gen_bool(REGION_EXPLORATION) => index,
+            _ => rng.gen_range(0..BLOCK_ENGINE_URLS.len()),
+        }
+    }
+
+    /// Re-measures every region each `interval` with a cheap `getTipAccounts` call.
+    pub fn spawn_probe(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                for (index, url) in BLOCK_ENGINE_URLS.iter().enumerate() {
+                    let start = Instant::now();
+                    match make_jito_request::<Value>("getTipAccounts", url, json!([])).await {
+                        Ok(_) => self.observe(index, start.elapsed()),
+                        Err(err) => tracing::debug!("jito probe of {} failed: {:?}", url, err),
+                    }
+                }
+                if let Some((index, ms)) = self.best() {
+                    info!("jito best region: {} ({:.0} ms)", BLOCK_ENGINE_URLS[index], ms);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
+
+/// What the block engines made of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleOutcome {
+    /// Accepted, with the bundle id from the first region that took it.
+    Accepted(String),
+    /// Refused as already processed, i.e. its transaction has landed.
+    Processed,
+    /// No region accepted it.
+    Rejected,
+}
+
+impl BundleOutcome {
+    pub fn bundle_id(&self) -> Option<String> {
+        match self {
+            BundleOutcome::Accepted(bundle_id) => Some(bundle_id.clone()),
+            _ => None,
+        }
+    }
 
+    /// Whether jito did its part: accepting the bundle or having landed it.
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, BundleOutcome::Rejected)
+    }
+}
+
+/// Sends a bundle and reports whether a block engine accepted it. With a
+/// region selector the bundle goes to the fastest region only, otherwise it
+/// is sent to every region concurrently and the first acceptance wins.
+pub async fn send_bundle(
+    bundle: Vec<VersionedTransaction>,
+    regions: Option<&JitoRegionSelector>,
+) -> anyhow::Result<BundleOutcome> {
     let bundle = bundle
         .into_iter()
         .map(|tx|  {
@@ -83,35 +177,45 @@ pub async fn send_bundle(bundle: Vec<VersionedTransaction>) -> anyhow::Result<()
             solana_sdk::bs58::encode(serialized).into_string()
         })
         .collect::<Vec<_>>();
+    let params = json!([bundle]);
 
-    let urls = [
-        "https://amsterdam.mainnet.block-engine.jito.wtf/api/v1/bundles",
-        "https://frankfurt.mainnet.block-engine.jito.wtf/api/v1/bundles",
-        "https://ny.mainnet.block-engine.jito.wtf/api/v1/bundles",
-        "https://slc.mainnet.block-engine.jito.wtf/api/v1/bundles",
-        "https://tokyo.mainnet.block-engine.jito.wtf/api/v1/bundles",
-    ];
-
-    let url = urls.choose(&mut rand::thread_rng()).expect("no URLs available");
-    
-    let result  = make_jito_request::<JitoResponse<String>>("sendBundle", url,json!([bundle])).await;
-
-    match result {
-        Ok((_response)) => {
-            tracing::info!("[Sending bundle] success!");
+    let indices = match regions {
+        Some(regions) => vec![regions.pick()],
+        None => (0..BLOCK_ENGINE_URLS.len()).collect(),
+    };
+    let results = join_all(indices.into_iter().map(|index| {
+        let params = params.clone();
+        async move {
+            let start = Instant::now();
+            let result = make_jito_request::<JitoResponse<String>>("sendBundle", BLOCK_ENGINE_URLS[index], params).await;
+            (index, start.elapsed(), result)
         }
-        Err(e) => {
-            if (e
-                .to_string()
-                .contains("bundle contains an already processed transaction"))
-            {
-                tracing::info!("bundle processed!");
-            } else {
-                tracing::debug!("send bundle failed: {:?}", e);
+    }))
+    .await;
+
+    let mut bundle_id = None;
+    let mut processed = false;
+    for (index, latency, result) in results {
+        match result {
+            Ok(response) => {
+                info!("[Sending bundle] {} accepted in {:?}, bundle id: {}", BLOCK_ENGINE_URLS[index], latency, response.result);
+                if let Some(regions) = regions {
+                    regions.observe(index, latency);
+                }
+                bundle_id.get_or_insert(response.result);
             }
+            Err(e) if e.to_string().contains("bundle contains an already processed transaction") => processed = true,
+            Err(e) => tracing::warn!("send bundle to {} failed after {:?}: {:?}", BLOCK_ENGINE_URLS[index], latency, e),
         }
-    };
-    Ok(())
+    }
+    Ok(match bundle_id {
+        Some(bundle_id) => BundleOutcome::Accepted(bundle_id),
+        None if processed => {
+            tracing::info!("bundle processed!");
+            BundleOutcome::Processed
+        }
+        None => BundleOutcome::Rejected,
+    })
 }
 
 pub fn build_bribe_ix(pubkey: &Pubkey, value: u64) -> solana_sdk::instruction::Instruction {
@@ -144,6 +248,20 @@ impl JitoTips {
     pub fn p25(&self) -> u64 {
         (self.p25_landed * 1e9f64) as u64
     }
+
+    /// Landed tip at one of the percentiles the stream reports (25, 50, 75,
+    /// 95 or 99), 0 before the stream delivered anything.
+    pub fn percentile(&self, percentile: u8) -> Option<u64> {
+        let sol = match percentile {
+            25 => self.p25_landed,
+            50 => self.p50_landed,
+            75 => self.p75_landed,
+            95 => self.p95_landed,
+            99 => self.p99_landed,
+            _ => return None,
+        };
+        Some((sol * 1e9f64) as u64)
+    }
 }
 
 impl std::fmt::Display for JitoTips {
@@ -209,3 +327,48 @@ pub async fn subscribe_jito_tips(tips: Arc<RwLock<JitoTips>>) -> JoinHandle<()>
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_selector_tracks_the_fastest_region() {
+        let regions = JitoRegionSelector::default();
+        assert_eq!(regions.best(), None);
+
+        regions.observe(0, Duration::from_millis(100));
+        regions.observe(3, Duration::from_millis(60));
+        assert_eq!(regions.best().unwrap().0, 3);
+
+        // one slow response moves the estimate by alpha, not all the way
+        regions.observe(3, Duration::from_millis(200));
+        let (index, ms) = regions.best().unwrap();
+        assert_eq!(index, 0);
+        assert!((ms - 100.0).abs() < 1e-6);
+        assert!(regions.pick() < BLOCK_ENGINE_URLS.len());
+    }
+
+    #[test]
+    fn processed_bundle_counts_as_healthy() {
+        assert!(BundleOutcome::Accepted("id".to_string()).is_ok());
+        assert!(BundleOutcome::Processed.is_ok());
+        assert!(!BundleOutcome::Rejected.is_ok());
+        assert_eq!(BundleOutcome::Processed.bundle_id(), None);
+        assert_eq!(BundleOutcome::Accepted("id".to_string()).bundle_id(), Some("id".to_string()));
+    }
+
+    #[test]
+    fn tip_percentiles_in_lamports() {
+        let tips: JitoTips = serde_json::from_str(
+            r#"{"landed_tips_25th_percentile": 0.0625, "landed_tips_50th_percentile": 0.125,
+                "landed_tips_75th_percentile": 0.25, "landed_tips_95th_percentile": 0.5,
+                "landed_tips_99th_percentile": 1.0}"#,
+        )
+        .unwrap();
+        assert_eq!(tips.p25(), 62_500_000);
+        assert_eq!(tips.percentile(50), Some(tips.p50()));
+        assert_eq!(tips.percentile(99), Some(1_000_000_000));
+        assert_eq!(tips.percentile(90), None);
+    }
+}
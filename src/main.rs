@@ -1,12 +1,40 @@
+mod balance;
+mod breakeven;
+mod config;
+mod error;
+mod explain;
+mod feature_log;
+mod grid;
+mod health;
 mod history;
+mod ipc;
 mod jito;
+mod metrics;
+mod notify;
+mod pnl;
 mod onchain_main;
 mod utils;
+mod webhook;
+mod window;
+mod pot;
 mod price;
+mod profile;
+mod ramp;
+mod req_id;
+mod shadow;
+mod snapshot;
+mod state;
+mod stats;
+mod tip;
 
 use clap::{command, Parser, Subcommand};
 
-use crate::history::get_history_winners;
+use crate::balance::{BalanceWatch, DailySpend};
+use crate::explain::{Explanation, Guard};
+use crate::feature_log::{FeatureLog, Features};
+use crate::grid::BoardGrid;
+use crate::health::{HealthState, Subsystem};
+use crate::history::{fetch_rounds, get_history_winners, get_top_history_winners};
 use crate::onchain_main::get_ore_refined_ix;
 use anchor_lang::declare_program;
 use anchor_lang::prelude::*;
@@ -21,7 +49,10 @@ use solana_client::{
     rpc_filter::{Memcmp, RpcFilterType},
 };
 use solana_sdk::commitment_config::CommitmentConfig;
-use solana_sdk::signature::Keypair;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::transaction::TransactionError;
 use solana_sdk::{
     compute_budget::ComputeBudgetInstruction,
     keccak::hash,
@@ -31,58 +62,294 @@ use solana_sdk::{
 };
 use spl_token::amount_to_ui_amount;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use solana_sdk::address_lookup_table::AddressLookupTableAccount;
 use solana_sdk::address_lookup_table::state::AddressLookupTable;
 use spl_associated_token_account::get_associated_token_address;
-use steel::{AccountDeserialize, Clock, Discriminator, Numeric};
-use tokio::select;
-use tokio::sync::Mutex;
-use tracing::info;
+use steel::{AccountDeserialize, Clock, Discriminator, Numeric, Zeroable};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, info_span, warn, Instrument};
 use utils::*;
-use crate::jito::send_bundle;
-use crate::price::get_price;
+use crate::ipc::{IpcEvent, IpcFeed};
+use crate::metrics::Metrics;
+use crate::notify::{Notifier, Secret};
+use crate::pnl::PnlTracker;
+use crate::jito::{send_bundle, subscribe_jito_tips, JitoRegionSelector, JitoTips};
+use crate::pot::PotEma;
+use crate::profile::LogFormat;
+use crate::price::{PriceCache, PriceSourceKind};
+use crate::ramp::Ramp;
+use crate::req_id::ReqIds;
+use crate::shadow::ShadowLedger;
+use crate::state::SessionState;
+use crate::stats::{JsonlStatsSink, NoopStatsSink, RunSummary, StatsEvent, StatsSink};
+use crate::tip::{FeeEscalation, TipController};
+use crate::webhook::{Decision, DeployConfirmed, Webhook};
+use crate::window::{CommitWindow, LandingLatency, SlotTime};
 
 declare_program!(ore_por_program);
 
 pub const DEFALUT_UNITS: u64 = 400_000;
+pub const MIN_ORE_REFINED_RATE: f64 = 1.1;
+const JITO_LANDING_TIMEOUT_SECS: u64 = 20;
+/// Consecutive failed polls after which deploys are paused.
+const POLL_FAILURE_THRESHOLD: u32 = 3;
+/// Caps the poll backoff at 2^4 = 16 seconds.
+const POLL_MAX_BACKOFF_SHIFT: u32 = 4;
+/// Slot-time samples needed before the measured slot time replaces --slot-time-ms.
+const SLOT_TIME_WARMUP: u64 = 5;
+/// Rounds of worst-case spend the wallet must cover before mining starts.
+const BALANCE_CHECK_ROUNDS: u64 = 3;
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
-    let args = Args::parse();
+    let argv = config::args_with_config(std::env::args().collect())?;
+    let args = Args::parse_from(argv);
+    let _profile_guard = profile::init_tracing(args.log_format, args.profile.as_deref())?;
+    if let Some(path) = &args.config {
+        info!("loaded flags from config {}", path);
+    }
     info!("Args: {:?}", args);
 
 
     let commitment = CommitmentConfig::processed();
 
-    // Build transaction
-    let rpc = Arc::new(RpcClient::new_with_commitment(
-        args.rpc
-            .parse()
-            .unwrap(),
-        commitment,
-    ));
-
-    let payer = Arc::new(read_keypair_file(args.keypair.clone()).unwrap());
-    get_balance(&rpc,&payer).await?;
+    let rpc = Arc::new(RpcClient::new_with_commitment(args.rpc.clone(), commitment));
+    // reads and polling stay on --rpc, deploys and their blockhashes go through --send-rpc
+    let send_rpc = match &args.send_rpc {
+        Some(url) => Arc::new(RpcClient::new_with_commitment(url.clone(), commitment)),
+        None => rpc.clone(),
+    };
+
+    if let Some(command) = args.command.clone() {
+        return match command {
+            Command::DumpRound { id, out } => snapshot::dump_round(&rpc, id, out).await,
+            Command::BreakEven { id, snapshot } => {
+                let round = match snapshot {
+                    Some(path) => {
+                        let snapshot = snapshot::RoundSnapshot::load(&path)?;
+                        let slots_left = snapshot.board()?.end_slot.saturating_sub(snapshot.clock()?.slot);
+                        println!("snapshot {} of round {} at slot {}, {} slots left", path, snapshot.round_id, snapshot.slot, slots_left);
+                        snapshot.round()?
+                    }
+                    None => {
+                        let round_id = match id {
+                            Some(id) => id,
+                            None => get_board(&rpc).await?.round_id,
+                        };
+                        get_round(&rpc, round_id).await?
+                    }
+                };
+                let (ore_price, sol_price) = price_cache(&args, tokio::time::Duration::ZERO).get_price_with_retry().await?;
+                breakeven::print_break_even(&round, ore_price, sol_price);
+                Ok(())
+            }
+            Command::FetchRounds { from, to, concurrency, cache_dir } => {
+                let ids = (from..=to).collect::<Vec<_>>();
+                let rounds = fetch_rounds(rpc.clone(), ids, concurrency, &cache_dir).await?;
+                let found = rounds.iter().filter(|(_, round)| round.is_some()).count();
+                info!("{} of {} rounds available, cached in {}", found, rounds.len(), cache_dir);
+                Ok(())
+            }
+            Command::History { rounds } => history::print_history(rounds).await,
+            Command::Watch { interval_ms } => watch(&rpc, tokio::time::Duration::from_millis(interval_ms)).await,
+        };
+    }
 
-    on_chain_main(&rpc, &payer,args).await?;
+    let mut keypair_paths = args.keypair.iter().cloned().collect::<Vec<_>>();
+    if let Some(path) = &args.keypairs {
+        keypair_paths.extend(read_keypair_list(path)?);
+    }
+    if keypair_paths.is_empty() {
+        let Some(pubkey) = &args.pubkey else {
+            anyhow::bail!("either --keypair, --keypairs or --pubkey is required");
+        };
+        // watch-only: show the wallet and stop, mining needs a signer
+        get_balance(&rpc, Pubkey::from_str(pubkey)?).await?;
+        info!("--pubkey is watch-only, pass --keypair to mine");
+        return Ok(());
+    }
+    let mut payers = Vec::with_capacity(keypair_paths.len());
+    for path in &keypair_paths {
+        let payer = read_keypair_file(path).map_err(|err| anyhow::anyhow!("failed to read keypair {}: {}", path, err))?;
+        payers.push(Arc::new(payer));
+    }
+    if payers.len() > 1 && (args.state_file.is_some() || args.feature_log.is_some()) {
+        anyhow::bail!("--state-file and --feature-log are per wallet, they can't be used with several keypairs");
+    }
+    for payer in &payers {
+        get_balance(&rpc, payer.pubkey()).await?;
+    }
 
-    select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received Ctrl+C, shutting down.");
+    let stats: Arc<dyn StatsSink> = match &args.stats_file {
+        Some(path) => Arc::new(JsonlStatsSink::open(path).await?),
+        None => Arc::new(NoopStatsSink),
+    };
+
+    let claim_on_shutdown = args.claim_on_shutdown;
+    let claim_timeout = tokio::time::Duration::from_secs(args.claim_timeout_secs);
+
+    // the first Ctrl+C lets the current iteration finish, a second one exits at once
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("Received Ctrl+C, finishing the in-flight deploy before shutting down. Press Ctrl+C again to exit now.");
+                shutdown.store(true, Ordering::Relaxed);
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        });
+    }
+    let notifier = Notifier::telegram(args.telegram_bot_token.clone(), args.telegram_chat_id.clone());
+    let ipc = IpcFeed::bind(args.ipc_socket.as_deref())?;
+    let wallet_metrics = payers.iter().map(|payer| Arc::new(Metrics::new(payer.pubkey()))).collect::<Vec<_>>();
+    // one cap for the whole run, however many wallets share it
+    let daily_spend = args.max_daily_spend_sol.map(|sol| Arc::new(Mutex::new(DailySpend::new((sol * 1e9f64) as u64))));
+    if let Some(port) = args.metrics_port {
+        metrics::serve((args.metrics_bind, port).into(), wallet_metrics.clone()).await?;
+    }
+    // before any task is spawned, a bad rate leaves nothing running behind
+    check_ore_refined_rate("ore_refined_rate", args.ore_refined_rate, args.max_ore_refined_rate)?;
+    if let Some(rate) = args.shadow_ore_refined_rate {
+        check_ore_refined_rate("shadow_ore_refined_rate", rate, args.max_ore_refined_rate)?;
+    }
+    let (polled, wallets) = initial_state(&rpc, &payers, &args).await?;
+    let _poller = AbortOnDrop(vec![update_state_loop(rpc.clone(), polled.clone(), wallets.clone(), args.clock_retries)]);
+    let (jito_feeds, _jito_tasks) = spawn_jito_feeds(&args).await?;
+    futures_util::future::try_join_all(payers.iter().zip(&wallet_metrics).zip(wallets).map(|((payer, metrics), wallet)| {
+        let span = info_span!("wallet", pubkey = %payer.pubkey());
+        on_chain_main(&rpc, &send_rpc, payer, stats.clone(), args.clone(), shutdown.clone(), metrics.clone(), notifier.clone(), ipc.clone(), daily_spend.clone(), polled.clone(), wallet, jito_feeds.clone()).instrument(span)
+    }))
+    .await?;
+
+    if claim_on_shutdown {
+        for payer in &payers {
+            match claim_all(&rpc, payer, claim_timeout).await {
+                Ok(true) => notifier.send(&format!("{}: claimed all rewards on shutdown", payer.pubkey())).await,
+                Ok(false) => {}
+                Err(err) => warn!("Claim on shutdown failed for {}: {:?}", payer.pubkey(), err),
+            }
         }
     }
+    if payers.len() > 1 {
+        for payer in &payers {
+            if let Err(err) = get_balance(&rpc, payer.pubkey()).await {
+                warn!("could not read final balance of {}: {:?}", payer.pubkey(), err);
+            }
+        }
+    }
+    stats.flush().await?;
 
     Ok(())
 }
 
 
+/// Prints the current round's per-square deployment every `interval` until Ctrl+C.
+async fn watch(rpc: &RpcClient, interval: tokio::time::Duration) -> anyhow::Result<()> {
+    let mut round_id = get_board(rpc).await?.round_id;
+    loop {
+        match get_all_state(rpc, &[], round_id).await {
+            Ok(state) => {
+                round_id = state.board.round_id;
+                match state.round.filter(|round| round.id == round_id) {
+                    Some(round) => println!(
+                        "round {} slot {} ({} slots left), {} SOL deployed\n{}",
+                        round_id,
+                        state.clock.slot,
+                        state.board.end_slot.saturating_sub(state.clock.slot),
+                        lamports_to_sol(round.total_deployed),
+                        BoardGrid::from(round.deployed)
+                    ),
+                    None => println!("round {} not created yet", round_id),
+                }
+            }
+            Err(err) => warn!("failed to read the board: {:?}", err),
+        }
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Keypair paths listed in `path`, one per line. Blank lines and lines
+/// starting with `#` are skipped.
+fn read_keypair_list(path: &str) -> anyhow::Result<Vec<String>> {
+    let list = std::fs::read_to_string(path)?;
+    Ok(list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn price_cache(args: &Args, min_refresh: tokio::time::Duration) -> PriceCache {
+    PriceCache::new(min_refresh, args.price_sources.iter().map(|kind| kind.source()).collect())
+}
+
+/// Why the price band forbids deploying, if it does. A stale cached price
+/// can't vouch for the band, so it counts as outside.
+fn price_band_violation(args: &Args, ore_price: f64, sol_price: f64, stale: bool) -> Option<String> {
+    let bands = [
+        ("ORE", ore_price, args.min_ore_price, args.max_ore_price),
+        ("SOL", sol_price, args.min_sol_price, args.max_sol_price),
+    ];
+    let configured = bands.iter().any(|(_, _, min, max)| min.is_some() || max.is_some());
+    if configured && stale {
+        return Some("price is stale, cannot check the price band".to_string());
+    }
+    for (token, price, min, max) in bands {
+        if min.is_some_and(|min| price < min) || max.is_some_and(|max| price > max) {
+            return Some(format!("{} price {} USDC outside [{:?}, {:?}]", token, price, min, max));
+        }
+    }
+    None
+}
+
+/// Why the jito copy of a deploy is not worth its tip, if it isn't. With
+/// `confirm_ms` set this waits that long for the rpc submission to confirm.
+async fn jito_suppression_reason(
+    rpc: &RpcClient,
+    rpc_signature: &Signature,
+    confirm_ms: Option<u64>,
+) -> Option<String> {
+    let confirm_ms = confirm_ms?;
+    let timeout = tokio::time::Duration::from_millis(confirm_ms);
+    match confirm_signature(rpc, rpc_signature, timeout).await {
+        Ok(Some(slot)) => Some(format!("rpc transaction {} confirmed in slot {} within {}ms", rpc_signature, slot, confirm_ms)),
+        Ok(None) => None,
+        Err(err) => {
+            info!("could not confirm rpc transaction {}: {:?}", rpc_signature, err);
+            None
+        }
+    }
+}
+
+/// Marks the rpc as failing and alerts when it just went from ok to failing.
+async fn report_rpc_failure(health: &Mutex<HealthState>, notifier: &Notifier, wallet: &Pubkey, action: &str) {
+    if health.lock().await.update(Subsystem::Rpc, false) {
+        notifier.notify(format!("{}: rpc failing ({}), deploys are skipped until it recovers", wallet, action));
+    }
+}
+
 async fn get_balance(
     rpc: &Arc<RpcClient>,
-    payer: &Arc<Keypair>,
+    wallet: Pubkey,
 ) -> anyhow::Result<()> {
-    let mut miner = get_miner(&rpc, payer.pubkey()).await?;
+    let Some(mut miner) = get_miner_opt(&rpc, wallet).await? else {
+        let sol_balance = rpc.get_balance(&wallet).await?;
+        info!(
+            "wallet: {:?} sol:{:.2} miner not registered, the first deploy registers it",
+            wallet,
+            amount_to_ui_amount(sol_balance, 9)
+        );
+        return Ok(());
+    };
 
     let treasury = get_treasury(&rpc).await?;
     if treasury.miner_rewards_factor > miner.rewards_factor {
@@ -95,17 +362,17 @@ async fn get_balance(
     }
 
 
-    let sol_balance = rpc.get_balance(&payer.pubkey()).await?;
+    let sol_balance = rpc.get_balance(&wallet).await?;
 
 
-    let ore_ata_address = get_associated_token_address(&payer.pubkey(), &pubkey!("oreoU2P8bN6jkk3jbaiVxYnG1dCXcYxwhwyK9jSybcp"));
+    let ore_ata_address = get_associated_token_address(&wallet, &pubkey!("oreoU2P8bN6jkk3jbaiVxYnG1dCXcYxwhwyK9jSybcp"));
     let ore_amount = rpc.get_token_account_balance(&ore_ata_address).await?;
     let wallet_ore = ore_amount.amount.parse::<u64>().unwrap_or(0);
 
 
 
     info!("wallet: {:?} sol:{:.2} unclaimed_sol:{}\t \t wallet_ore:{:.2} \t unclaimed_ore: {:.2} \t refined_ore: {:.2}",
-                payer.pubkey(),
+                wallet,
                 amount_to_ui_amount(sol_balance, 9),
                 amount_to_ui_amount(miner.rewards_sol, 9),
                 amount_to_ui_amount(wallet_ore, TOKEN_DECIMALS),
@@ -117,188 +384,1374 @@ async fn get_balance(
 }
 
 
+/// Rejects a rate outside [MIN_ORE_REFINED_RATE, max] and warns when it is
+/// within 5% of either bound.
+fn check_ore_refined_rate(name: &str, rate: f64, max: f64) -> anyhow::Result<()> {
+    if !(MIN_ORE_REFINED_RATE..=max).contains(&rate) {
+        anyhow::bail!("{} {} is outside [{}, {}]", name, rate, MIN_ORE_REFINED_RATE, max);
+    }
+    if rate < MIN_ORE_REFINED_RATE * 1.05 || rate > max * 0.95 {
+        warn!("{} {} is close to the allowed bounds [{}, {}]", name, rate, MIN_ORE_REFINED_RATE, max);
+    }
+    Ok(())
+}
+
+/// Most one round can cost: the refined program may spread the deploy over
+/// every square, each paying the program fee, plus the priority fee at the
+/// compute unit ceiling, the highest jito tip unless jito is off and the
+/// signature fees.
+fn worst_case_round_cost(args: &Args, deploy_amount: u64) -> u64 {
+    let mut deployed = deploy_amount * 25;
+    if let Some(cap) = args.max_total_deploy_per_round_sol {
+        deployed = deployed.min((cap * 1e9f64) as u64);
+    }
+    let program_fee = deployed * 5 / 1000;
+    let unit_price = if args.cu_price_escalation > 1.0 { args.max_cu_price.max(PRIORITY_FEE) } else { PRIORITY_FEE };
+    let priority_fee = args.max_compute_units.max(args.min_compute_units) * unit_price / 1_000_000;
+    let jito_tip = if args.no_jito { 0 } else { args.jito_tip_max };
+    deployed + program_fee + priority_fee + jito_tip + 2 * 5000
+}
+
+/// The controller's tip is the floor, the tip stream can only raise it up to
+/// `max`, the ceiling `worst_case_round_cost` assumes.
+fn jito_tip(controller_tip: u64, stream_tip: Option<u64>, max: u64) -> u64 {
+    controller_tip.max(stream_tip.unwrap_or(0).min(max))
+}
+
 async fn on_chain_main(
     rpc: &Arc<RpcClient>,
+    send_rpc: &Arc<RpcClient>,
     payer: &Arc<Keypair>,
-    args: Args
+    stats: Arc<dyn StatsSink>,
+    args: Args,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+    notifier: Notifier,
+    ipc: IpcFeed,
+    daily_spend: Option<Arc<Mutex<DailySpend>>>,
+    polled: PolledState,
+    wallet: WalletState,
+    jito_feeds: JitoFeeds,
 ) -> anyhow::Result<()> {
-    let board_mutex = Arc::new(Mutex::new(get_board(&rpc).await?));
-    let clock_mutex = Arc::new(Mutex::new(get_clock(&rpc).await?));
-    let miner_mutex = Arc::new(Mutex::new(get_miner(&rpc,payer.pubkey()).await?));
-    let round_mutex = Arc::new(Mutex::new(get_round(&rpc,board_mutex.lock().await.round_id).await?));
+    if args.startup_jitter_ms > 0 {
+        let jitter = rand::thread_rng().gen_range(0..=args.startup_jitter_ms);
+        info!("startup jitter: {} ms", jitter);
+        tokio::time::sleep(tokio::time::Duration::from_millis(jitter)).await;
+    }
 
-    update_board_loop(rpc.clone(), board_mutex.clone()).await?;
-    update_clock_loop(rpc.clone(), clock_mutex.clone()).await?;
-    update_miner_loop(rpc.clone(), payer.clone(),miner_mutex.clone()).await?;
-    update_round_loop(rpc.clone(), round_mutex.clone(),board_mutex.clone()).await?;
+    let PolledState {
+        board: board_mutex,
+        clock: clock_mutex,
+        round: round_mutex,
+    } = polled;
+    let WalletState {
+        miner: miner_mutex,
+        health,
+        state_changed,
+        ..
+    } = wallet;
+
+    let blockhash_cache = Arc::new(BlockhashCache::new(args.max_blockhash_age_slots));
+    let _monitors = AbortOnDrop(vec![
+        update_blockhash_loop(send_rpc.clone(), blockhash_cache.clone(), clock_mutex.clone()).await?,
+    ]);
+
+
+    let mut deploy_amount = sol_to_lamports_rounded(
+        args.per_round_deploy_amount.expect("per_round_deploy_amount is required when mining"),
+        args.amount_rounding,
+    );
+    if deploy_amount < args.min_deploy_lamports {
+        match args.below_min_deploy {
+            BelowMinDeploy::Error => anyhow::bail!(
+                "deploy amount {} lamports is below the minimum {} lamports",
+                deploy_amount, args.min_deploy_lamports
+            ),
+            BelowMinDeploy::Bump => {
+                warn!("deploy amount {} lamports bumped to the minimum {} lamports", deploy_amount, args.min_deploy_lamports);
+                deploy_amount = args.min_deploy_lamports;
+            }
+        }
+    }
+    info!("deploy amount: {} lamports per round", deploy_amount);
+    if !args.ignore_balance_check {
+        let needed = worst_case_round_cost(&args, deploy_amount) * BALANCE_CHECK_ROUNDS;
+        let balance = rpc.get_balance(&payer.pubkey()).await?;
+        if balance < needed {
+            anyhow::bail!(
+                "wallet {} has {} SOL, {} rounds at worst case need {} SOL ({} SOL short), pass --ignore-balance-check to start anyway",
+                payer.pubkey(),
+                lamports_to_sol(balance),
+                BALANCE_CHECK_ROUNDS,
+                lamports_to_sol(needed),
+                lamports_to_sol(needed - balance)
+            );
+        }
+    }
 
+    let mut avoid_miners = args
+        .avoid_miners
+        .iter()
+        .map(|pubkey| Pubkey::from_str(pubkey))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.avoid_top_miners > 0 {
+        match get_top_history_winners(args.avoid_top_miners).await {
+            Ok(winners) => avoid_miners.extend(winners),
+            Err(err) => warn!("failed to load top miners from history, avoid_top_miners disabled: {:?}", err),
+        }
+    }
+    avoid_miners.retain(|pubkey| *pubkey != payer.pubkey());
+    if !avoid_miners.is_empty() {
+        info!("avoiding rounds with any of these miners: {:?}", avoid_miners);
+    }
 
+    let mut preflight_pending = args.preflight_check;
+    let mut explained_round_id = None;
+    let mut intended_deploy: Option<(u64, [u64; 25])> = None;
+    let mut feature_log = args.feature_log.as_deref().map(FeatureLog::open).transpose()?;
+    let mut pending_features: Option<Features> = None;
+    let max_total_deploy = args.max_total_deploy_per_round_sol.map(|sol| (sol * 1e9f64) as u64);
+    let mut daily_cap_notified = None;
+    let landing_latency = Arc::new(Mutex::new(LandingLatency::default()));
+    let jito_enabled = !args.no_jito;
+    if !jito_enabled {
+        warn!("jito disabled, deploys go through the rpc only");
+    }
+    let JitoFeeds {
+        tips: jito_tips,
+        regions: jito_regions,
+    } = jito_feeds;
+    let webhook = args.deploy_webhook.clone().map(|url| Arc::new(Webhook::new(url)));
+    let mut lookup_tables = vec![];
+    for address in &args.lookup_table {
+        if args.tx_version == TxVersion::Legacy {
+            anyhow::bail!("--lookup-table needs v0 transactions, not --tx-version legacy");
+        }
+        let table = get_lookup_table(&rpc, Pubkey::from_str(address)?).await?;
+        info!("using lookup table {} with {} addresses", table.key, table.addresses.len());
+        lookup_tables.push(table);
+    }
+    let lookup_tables = Arc::new(lookup_tables);
+    let tip_controller = Arc::new(Mutex::new(TipController::new(args.jito_tip_min, args.jito_tip_max, args.adaptive_tip)));
+    let mut fee_escalation = FeeEscalation::new(PRIORITY_FEE, args.cu_price_escalation, args.max_cu_price);
     let mut last_round_id = 0_u64;
-    let mut req_id = 0;
-    let (mut ore_price,mut sol_price) = get_price().await?;
+    let mut deployed_round_id = None;
+    let mut landed_round_noted = None;
+    let mut prev_miner = miner_mutex.lock().await.clone();
+    let mut budget: Option<RoundBudget> = None;
+    let mut shadow = args.shadow_ore_refined_rate.map(ShadowLedger::new);
+    let mut loss_cooldown_until = 0;
+    let mut dry_run_round: Option<(u64, u64)> = None;
+    let mut dry_run_total = 0;
+    let mut ramp = Ramp::new(args.ore_refined_rate, args.observe_rounds, args.go_live_min_win_rate, args.go_live_confirm_file.clone());
+    let mut pot_ema = PotEma::new(args.pot_ema_warmup_rounds);
+    if args.max_pot_multiple.is_some() && args.pot_ema_warmup_rounds > 0 {
+        match args.pot_reference_sol {
+            Some(sol) => info!("pot ema warming up for {} rounds, comparing against {} SOL until then", args.pot_ema_warmup_rounds, sol),
+            None => info!("pot ema warming up for {} rounds, max_pot_multiple inactive until then", args.pot_ema_warmup_rounds),
+        }
+    }
+    let mut session = SessionState::default();
+    if let Some(path) = &args.state_file {
+        match SessionState::load(path, tokio::time::Duration::from_secs(args.state_max_age_secs)) {
+            Ok(Some(state)) => {
+                if let Some(daily_spend) = &daily_spend {
+                    daily_spend.lock().await.restore(&state.daily_spends);
+                }
+                // a stale state comes back unsaved, carrying only the daily spends
+                if state.saved_at > 0 {
+                    pot_ema.restore(state.pot_ema, state.pot_rounds);
+                    landing_latency.lock().await.restore(state.landing_latency_slots);
+                    if let Some(tip) = state.jito_tip {
+                        tip_controller.lock().await.restore(tip);
+                    }
+                    loss_cooldown_until = state.loss_cooldown_until;
+                    info!(
+                        "resuming after round {}: {} SOL spent, {} SOL and {} ORE earned so far",
+                        state.last_round_id,
+                        lamports_to_sol(state.spent),
+                        lamports_to_sol(state.earned_sol),
+                        amount_to_ui_amount(state.earned_ore, TOKEN_DECIMALS)
+                    );
+                }
+                session = state;
+            }
+            Ok(None) => {}
+            Err(err) => warn!("failed to load session state from {}: {:?}", path, err),
+        }
+    }
+    // rounds deployed into before a restart, never deploy into them again
+    let resumed_rounds = session.deployed_rounds.clone();
+    let mut balance_watch = args.min_balance_alert_sol.map(|sol| BalanceWatch::new((sol * 1e9f64) as u64));
+    let mut req_ids = ReqIds::new(args.req_id_space);
+    let mut price_cache = price_cache(&args, tokio::time::Duration::from_secs(args.price_refresh_secs));
+    let (mut ore_price,mut sol_price) = price_cache.get_price_with_retry().await?;
+    info!(
+        "at current prices 1 ORE = {:.4} SOL, ore_refined_rate {} accepts spending up to {:.4} SOL worth of ORE per unclaimed ORE",
+        ore_price / sol_price,
+        args.ore_refined_rate,
+        args.ore_refined_rate * ore_price / sol_price
+    );
 
+    let mut slot_time = SlotTime::new(args.slot_time_ms, SLOT_TIME_WARMUP);
+    let auto_claim_sol = args.auto_claim_sol.map(|sol| (sol * 1e9f64) as u64);
+    let auto_claim_ore = args.auto_claim_ore.map(|ore| spl_token::ui_amount_to_amount(ore, TOKEN_DECIMALS));
+    let mut summary = RunSummary::default();
+    let mut pnl = PnlTracker::new();
+    let mut rounds_seen = 0u64;
+    let mut in_flight = JoinSet::new();
+    // deploy tasks report the full cost of every deploy that landed, booked once here
+    let (landed_tx, mut landed_rx) = tokio::sync::mpsc::unbounded_channel::<(u64, u64)>();
     loop {
-        req_id += 1;
-        req_id = req_id % 100;
+        while in_flight.try_join_next().is_some() {}
+        if book_landed(&mut landed_rx, &mut balance_watch, &mut session, &mut summary, &mut pnl, &metrics) {
+            save_session(&mut session, args.state_file.as_deref(), daily_spend.as_deref()).await;
+        }
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
         // checkpoint(rpc.clone(), payer, miner_mutex.clone(), board_mutex.clone()).await?;
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-        let board = board_mutex.lock().await.clone();
-        let clock = clock_mutex.lock().await.clone();
-        let miner = miner_mutex.lock().await.clone();
+        // wait until the board or clock loop reports a new round / new slot
+        let data_ok = tokio::time::timeout(tokio::time::Duration::from_secs(5), state_changed.notified()).await.is_ok();
+        {
+            let mut health = health.lock().await;
+            health.update(Subsystem::Data, data_ok);
+            metrics.observe_health(&health);
+        }
+        if let Some(best) = jito_regions.as_ref().and_then(|regions| regions.best()) {
+            metrics.observe_jito_region(best);
+        }
+        if !data_ok {
+            continue;
+        }
+        let (board, clock, miner) = async {
+            (
+                board_mutex.lock().await.clone(),
+                clock_mutex.lock().await.clone(),
+                miner_mutex.lock().await.clone(),
+            )
+        }
+        .instrument(info_span!("snapshot"))
+        .await;
         let round_id = board.round_id;
+        slot_time.observe(clock.slot, std::time::Instant::now());
+        if ipc.is_enabled() {
+            let round = round_mutex.lock().await;
+            if round.id == round_id {
+                ipc.publish(&IpcEvent::Snapshot {
+                    wallet: payer.pubkey().to_string(),
+                    round_id,
+                    slot: clock.slot,
+                    slots_remaining: board.end_slot.saturating_sub(clock.slot),
+                    total_deployed: round.total_deployed,
+                    deployed: round.deployed,
+                });
+            }
+        }
+
+        Metrics::set(&metrics.unclaimed_ore, miner.rewards_ore + miner.refined_ore);
+        let events = stats::miner_events(&prev_miner, &miner);
+        // a checkpoint settles the last deployed round, no win with it means a loss
+        if args.loss_cooldown_rounds > 0 && miner.checkpoint_id > prev_miner.checkpoint_id {
+            let won = events.iter().any(|event| matches!(event, StatsEvent::Win { .. }));
+            if !won {
+                loss_cooldown_until = round_id + args.loss_cooldown_rounds;
+                info!(
+                    "checkpoint of round {} realized a loss, pausing until round {}",
+                    miner.checkpoint_id, loss_cooldown_until
+                );
+            }
+        }
+        for event in events {
+            if let StatsEvent::Win { sol, ore, .. } = &event {
+                session.earned_sol += sol;
+                session.earned_ore += ore;
+                summary.record_win(*sol, *ore);
+            }
+            if let StatsEvent::Claim { sol, ore, .. } = &event {
+                notifier.notify(format!(
+                    "{}: claimed {} SOL and {} ORE",
+                    payer.pubkey(), lamports_to_sol(*sol), amount_to_ui_amount(*ore, TOKEN_DECIMALS)
+                ));
+            }
+            info!("{:?}", event);
+            stats::record(stats.as_ref(), event).await;
+        }
+        pnl.observe_miner(&prev_miner, &miner);
+        prev_miner = miner;
 
 
         if last_round_id != round_id {
             info!("New round detected: {}", round_id);
+            // settle the previous round and refresh the price at the same time,
+            // a failed price refresh keeps the last price instead of blocking
+            let (prev_round, price) = tokio::join!(
+                async {
+                    match last_round_id {
+                        0 => None,
+                        id => Some(get_round(&rpc, id).await),
+                    }
+                },
+                price_cache.get_price_with_retry(),
+            );
+            if let Some(prev_round) = prev_round {
+                match prev_round {
+                    Ok(prev_round) => {
+                        info!("round {} final distribution (SOL):\n{}", last_round_id, BoardGrid::from(prev_round.deployed));
+                        pot_ema.observe(prev_round.total_deployed);
+                        if let (Some(feature_log), Some(features)) = (feature_log.as_mut(), pending_features.take()) {
+                            if features.round_id == prev_round.id {
+                                if let Err(err) = feature_log.write(&features, &prev_round) {
+                                    warn!("failed to write feature log: {:?}", err);
+                                }
+                            }
+                        }
+                        if let Some(event) = shadow.as_mut().and_then(|s| s.settle(&prev_round)) {
+                            stats::record(stats.as_ref(), event).await;
+                        }
+                        if let Some(event) = ramp.settle(&prev_round) {
+                            stats::record(stats.as_ref(), event).await;
+                        }
+                    }
+                    Err(err) => info!("get round {} failed: {:?}", last_round_id, err),
+                }
+            }
+            if let Some((deployed_round, expected)) = intended_deploy.take() {
+                if miner.round_id != deployed_round {
+                    warn!("verify: deploy for round {} did not land (miner is at round {})", deployed_round, miner.round_id);
+                } else if miner.deployed != expected {
+                    warn!(
+                        "verify: on-chain position in round {} differs from the intended deploy\nintended (SOL):\n{}on-chain (SOL):\n{}",
+                        deployed_round, BoardGrid::from(expected), BoardGrid::from(miner.deployed)
+                    );
+                } else {
+                    info!("verify: round {} position matches the intended deploy", deployed_round);
+                }
+            }
+            if let Some((id, spend)) = dry_run_round.filter(|(id, _)| *id == last_round_id) {
+                info!(
+                    "dry run: round {} would have spent {} SOL, {} SOL this session",
+                    id, lamports_to_sol(spend), lamports_to_sol(dry_run_total)
+                );
+            }
             last_round_id = round_id;
-            (ore_price,sol_price) = get_price().await?;
-            info!("ORE price: {} USDC", ore_price);
-            info!("SOL price: {} USDC", sol_price);
+            if let Some(path) = &args.state_file {
+                session.pot_ema = pot_ema.value();
+                session.pot_rounds = pot_ema.rounds;
+                session.landing_latency_slots = landing_latency.lock().await.ema();
+                session.jito_tip = Some(tip_controller.lock().await.tip());
+                session.loss_cooldown_until = loss_cooldown_until;
+                session.last_round_id = last_round_id;
+                save_session(&mut session, Some(path.as_str()), daily_spend.as_deref()).await;
+            }
+            if args.max_rounds.is_some_and(|max_rounds| summary.rounds >= max_rounds) {
+                info!("played {} rounds, stopping", summary.rounds);
+                break;
+            }
+            Metrics::inc(&metrics.rounds_seen);
+            rounds_seen += 1;
+            if args.pnl_every_rounds.is_some_and(|every| every > 0 && rounds_seen % every == 0) {
+                let ore_in_sol = ore_price / sol_price;
+                info!(
+                    "pnl: {:+.6} SOL this hour, {:+.6} SOL since start (ORE = {:.6} SOL)",
+                    pnl.current_hour().net_sol(ore_in_sol), pnl.total().net_sol(ore_in_sol), ore_in_sol
+                );
+            }
+            // runs once per round, between rounds, so a claim is never sent twice in one round
+            let claim_sol_due = auto_claim_sol.is_some_and(|threshold| miner.rewards_sol >= threshold);
+            let claim_ore_due = auto_claim_ore.is_some_and(|threshold| miner.rewards_ore + miner.refined_ore >= threshold);
+            if (claim_sol_due || claim_ore_due) && !args.dry_run {
+                info!(
+                    "round {}: auto claim of {} SOL and {} ORE",
+                    round_id,
+                    lamports_to_sol(miner.rewards_sol),
+                    amount_to_ui_amount(miner.rewards_ore + miner.refined_ore, TOKEN_DECIMALS)
+                );
+                let rpc = rpc.clone();
+                let payer = payer.clone();
+                let notifier = notifier.clone();
+                let timeout = tokio::time::Duration::from_secs(args.claim_timeout_secs);
+                in_flight.spawn(async move {
+                    match claim_all(&rpc, &payer, timeout).await {
+                        Ok(true) => notifier.notify(format!("{}: auto claim confirmed", payer.pubkey())),
+                        Ok(false) => {}
+                        Err(err) => warn!("auto claim failed: {:?}", err),
+                    }
+                });
+            }
+            if balance_watch.is_some() || args.metrics_port.is_some() {
+                match rpc.get_balance(&payer.pubkey()).await {
+                    Ok(balance) => {
+                        Metrics::set(&metrics.sol_balance, balance);
+                        if let Some(alert) = balance_watch.as_mut().and_then(|watch| watch.check(balance)) {
+                            warn!("{}", alert);
+                            notifier.notify(format!("{}: {}", payer.pubkey(), alert));
+                        }
+                    }
+                    Err(err) => info!("get balance failed: {:?}", err),
+                }
+            }
+            match price {
+                Ok(price) => {
+                    (ore_price,sol_price) = price;
+                    info!("ORE price: {} USDC", ore_price);
+                    info!("SOL price: {} USDC", sol_price);
+                }
+                Err(err) => warn!("get price failed: {:?}, keeping ORE {} / SOL {} USDC", err, ore_price, sol_price),
+            }
         }
 
 
 
 
-        let slot_left = board.end_slot.saturating_sub(clock.slot);
+        if round_id <= loss_cooldown_until {
+            continue;
+        }
 
-        info!("round_id: {:?} slot_left: {:?}", round_id, slot_left);
+        let slot_left = board.end_slot.saturating_sub(clock.slot);
 
         if slot_left > args.remaining_slots as u64 {
+            info!("round_id: {:?} slot_left: {:?}", round_id, slot_left);
+            continue;
+        }
+
+        let commit_window = if args.commit_window {
+            let latency = landing_latency.lock().await.slots();
+            let window = CommitWindow::new(board.end_slot, latency, args.landing_margin_slots);
+            info!(
+                "round {}: commit window closes at slot {}, now slot {}",
+                round_id, window.latest_safe_slot, clock.slot
+            );
+            if window.too_late(clock.slot) {
+                info!("round {}: too late to land, slot {} > latest safe slot {}", round_id, clock.slot, window.latest_safe_slot);
+                continue;
+            }
+            Some(window)
+        } else {
+            None
+        };
+
+        if budget.as_ref().is_none_or(|b| b.round_id != round_id) {
+            budget = Some(RoundBudget::new(round_id, tokio::time::Duration::from_millis(args.round_budget_ms)));
+        }
+        let round_budget = budget.as_ref().unwrap();
+        let time_left = slot_time.time_for(slot_left);
+        let daily_spend_left = match &daily_spend {
+            Some(daily_spend) => Some(daily_spend.lock().await.remaining()),
+            None => None,
+        };
+        match daily_spend_left {
+            Some(daily_spend_left) => info!(
+                round_id,
+                slots_remaining = slot_left,
+                "round_id: {:?} slot_left: {:?} (~{:.1?} at {:.0}ms/slot) budget_left: {:?} daily_spend_left: {} SOL",
+                round_id, slot_left, time_left, slot_time.ms(), round_budget.remaining(), lamports_to_sol(daily_spend_left)
+            ),
+            None => info!(
+                round_id,
+                slots_remaining = slot_left,
+                "round_id: {:?} slot_left: {:?} (~{:.1?} at {:.0}ms/slot) budget_left: {:?}",
+                round_id, slot_left, time_left, slot_time.ms(), round_budget.remaining()
+            ),
+        }
+        if round_budget.exhausted() {
+            continue;
+        }
+
+        if resumed_rounds.contains(&round_id) {
+            continue;
+        }
+
+        // once a deploy for this round is on the miner account, any further
+        // submission could only fail with AlreadyDeploy
+        if miner.round_id == round_id && miner.deployed.iter().any(|d| *d > 0) {
+            if landed_round_noted != Some(round_id) {
+                landed_round_noted = Some(round_id);
+                info!("round {}: miner already deployed {} SOL, waiting for the next round", round_id, lamports_to_sol(miner.deployed.iter().sum()));
+            }
+            continue;
+        }
+
+        if health.lock().await.is_degraded(Subsystem::Poll) {
+            warn!("round {}: account polling is failing, not deploying on stale data", round_id);
+            continue;
+        }
+
+        if args.strict {
+            if slot_left < args.strict_min_slots {
+                continue;
+            }
+            if deployed_round_id == Some(round_id) {
+                continue;
+            }
+        }
+
+        let (pot, populated_squares) = {
+            let round = round_mutex.lock().await;
+            if feature_log.is_some() && round.id == round_id {
+                pending_features = Some(Features {
+                    round_id,
+                    slot: clock.slot,
+                    slot_left,
+                    ore_price,
+                    sol_price,
+                    total_deployed: round.total_deployed,
+                    deployed: round.deployed,
+                });
+            }
+            (round.total_deployed, round.deployed.iter().filter(|d| **d > 0).count())
+        };
+        if populated_squares < args.min_populated_squares {
+            info!(
+                "board too sparse to discriminate: {} of 25 squares have deployments (< {}), deferring",
+                populated_squares, args.min_populated_squares
+            );
+            continue;
+        }
+        info!("pot: {} SOL, pot ema: {:?} SOL", lamports_to_sol(pot), pot_ema.value().map(|ema| ema / 1e9));
+        if pot < (args.min_pot_sol * 1e9f64) as u64 {
+            info!("pot {} SOL below min_pot_sol {}, skip", lamports_to_sol(pot), args.min_pot_sol);
+            continue;
+        }
+        let pot_reference = pot_ema.trusted().or(args.pot_reference_sol.map(|sol| sol * 1e9));
+        if let (Some(multiple), Some(reference)) = (args.max_pot_multiple, pot_reference) {
+            if pot as f64 > reference * multiple {
+                warn!(
+                    "pot {} SOL is more than {}x the {} {:.4} SOL, skip round {}",
+                    lamports_to_sol(pot),
+                    multiple,
+                    if pot_ema.trusted().is_some() { "recent average" } else { "static reference" },
+                    reference / 1e9,
+                    round_id
+                );
+                continue;
+            }
+        }
+
+        if let Some(target) = args.wait_for_pot_sol {
+            let target = (target * 1e9f64) as u64;
+            let deadline = slot_left <= args.wait_for_pot_deadline_slots
+                || commit_window.is_some_and(|window| clock.slot >= window.latest_safe_slot);
+            if pot < target && !deadline {
+                info!("round {}: pot {} SOL below target {}, waiting ({} slots left)", round_id, lamports_to_sol(pot), lamports_to_sol(target), slot_left);
+                continue;
+            }
+            info!(
+                "round {}: committing at pot {} SOL ({}), {} slots left",
+                round_id,
+                lamports_to_sol(pot),
+                if pot >= target { "target reached" } else { "deadline reached" },
+                slot_left
+            );
+        }
+
+        if !avoid_miners.is_empty() {
+            match round_budget.spend(get_miners(&rpc, &avoid_miners).instrument(info_span!("avoid_miners", round_id))).await {
+                Ok(miners) => {
+                    let present = miners
+                        .iter()
+                        .flatten()
+                        .find(|m| m.round_id == round_id && m.deployed.iter().any(|d| *d > 0));
+                    if let Some(miner) = present {
+                        info!("tracked miner {} deployed in round {}, skip", miner.authority, round_id);
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    info!("get tracked miners failed: {:?}", err);
+                    continue;
+                }
+            }
+        }
+
+        (ore_price,sol_price) = match round_budget.spend(price_cache.get_price_with_retry().instrument(info_span!("price", round_id))).await {
+            Ok(price) => price,
+            Err(err) => {
+                info!("get price failed: {:?}", err);
+                health.lock().await.update(Subsystem::Price, false);
+                continue;
+            }
+        };
+        health.lock().await.update(Subsystem::Price, !price_cache.is_stale());
+        if let Some(reason) = price_band_violation(&args, ore_price, sol_price, price_cache.is_stale()) {
+            info!("round {}: {}, skip", round_id, reason);
             continue;
         }
 
-        let checkpoint_ix = checkpoint(payer.pubkey(), payer.pubkey(), miner.round_id);
-        let refined_ix = get_ore_refined_ix(
-            payer.pubkey(),
-            round_id,
-            ore_price,
-            sol_price,
-            (args.per_round_deploy_amount * 1e9f64) as u64,
-            args.remaining_slots,
-            args.ore_refined_rate,
-            req_id,
-        )?;
+        let Some(req_id) = req_ids.next(clock.slot) else {
+            warn!("every req_id is carried by a transaction that could still land, skip");
+            continue;
+        };
+        let checkpoint_ix = match args.checkpoint_mode {
+            CheckpointMode::Always => Some(checkpoint(payer.pubkey(), payer.pubkey(), miner.round_id)),
+            CheckpointMode::WhenNeeded if miner.checkpoint_id < miner.round_id => {
+                Some(checkpoint(payer.pubkey(), payer.pubkey(), miner.round_id))
+            }
+            CheckpointMode::WhenNeeded => None,
+        };
+        let refined_ix = info_span!("build", round_id).in_scope(|| {
+            get_ore_refined_ix(
+                payer.pubkey(),
+                round_id,
+                ore_price,
+                sol_price,
+                deploy_amount,
+                args.remaining_slots,
+                args.ore_refined_rate,
+                req_id,
+            )
+        })?;
         let claim_sol_ix = claim_sol(payer.pubkey());
-        let ixs = [checkpoint_ix.clone(),refined_ix.clone(),claim_sol_ix];
+        let ixs = checkpoint_ix.iter().cloned().chain([refined_ix.clone(), claim_sol_ix]).collect::<Vec<_>>();
 
         if slot_left > 1 {
-            let simulate_result = simulate_transaction(&rpc, &payer, &ixs).await?;
-            let mut units_consumed = simulate_result.value.units_consumed.unwrap_or(0);
-            units_consumed = (units_consumed * 11 / 10).max(200_000);
+            let simulate_result = match round_budget.spend(simulate_transaction(&rpc, &payer, &ixs).instrument(info_span!("simulate", round_id))).await {
+                Ok(result) => result,
+                Err(err) => {
+                    info!("simulate transaction failed: {:?}", err);
+                    report_rpc_failure(&health, &notifier, &payer.pubkey(), "simulate").await;
+                    continue;
+                }
+            };
+            health.lock().await.update(Subsystem::Rpc, true);
+
+            // the refined program decides how many squares `deploy_amount` goes to,
+            // so the round total is only known from the simulation
+            let mut deploy_amount = deploy_amount;
+            let mut ixs = ixs;
+            let mut simulate_result = simulate_result;
+            if let Some(cap) = max_total_deploy {
+                let total = simulated_miner_deployed(&simulate_result.value).map_or(0, |d| d.iter().sum::<u64>());
+                if total > cap {
+                    if args.total_cap_action == TotalCapAction::Skip {
+                        info!("round {}: deploy of {} SOL exceeds max_total_deploy_per_round_sol {}, skip", round_id, lamports_to_sol(total), lamports_to_sol(cap));
+                        continue;
+                    }
+                    let scaled = (deploy_amount as u128 * cap as u128 / total as u128) as u64;
+                    info!(
+                        "round {}: deploy of {} SOL exceeds max_total_deploy_per_round_sol {}, scaling amount {} -> {} SOL",
+                        round_id, lamports_to_sol(total), lamports_to_sol(cap), lamports_to_sol(deploy_amount), lamports_to_sol(scaled)
+                    );
+                    deploy_amount = scaled;
+                    let refined_ix = get_ore_refined_ix(
+                        payer.pubkey(),
+                        round_id,
+                        ore_price,
+                        sol_price,
+                        deploy_amount,
+                        args.remaining_slots,
+                        args.ore_refined_rate,
+                        req_id,
+                    )?;
+                    ixs = checkpoint_ix.iter().cloned().chain([refined_ix, claim_sol(payer.pubkey())]).collect();
+                    simulate_result = match round_budget.spend(simulate_transaction(&rpc, &payer, &ixs)).await {
+                        Ok(result) => result,
+                        Err(err) => {
+                            info!("simulate transaction failed: {:?}", err);
+                            continue;
+                        }
+                    };
+                    let total = simulated_miner_deployed(&simulate_result.value).map_or(0, |d| d.iter().sum::<u64>());
+                    if total > cap {
+                        info!("round {}: scaled deploy of {} SOL still exceeds the cap, skip", round_id, lamports_to_sol(total));
+                        continue;
+                    }
+                }
+            }
+
+            let round_total = simulated_miner_deployed(&simulate_result.value).map_or(deploy_amount, |d| d.iter().sum::<u64>());
+            if let Some(daily_spend) = &daily_spend {
+                let remaining = daily_spend.lock().await.remaining();
+                if round_total > remaining {
+                    warn!(
+                        "round {}: deploy of {} SOL exceeds the {} SOL left of max_daily_spend_sol, skip",
+                        round_id, lamports_to_sol(round_total), lamports_to_sol(remaining)
+                    );
+                    if daily_cap_notified != Some(round_id) {
+                        daily_cap_notified = Some(round_id);
+                        notifier.notify(format!(
+                            "{}: round {} skipped, daily spend cap reached ({} SOL left)",
+                            payer.pubkey(), round_id, lamports_to_sol(remaining)
+                        ));
+                    }
+                    continue;
+                }
+            }
+
+            let simulated_units = simulate_result.value.units_consumed.unwrap_or(0);
+            let units_consumed = compute_units_for(simulated_units, args.min_compute_units, args.max_compute_units);
+            if simulated_units * 11 / 10 > units_consumed {
+                warn!("simulated {} units, capping the limit at {}", simulated_units, units_consumed);
+            }
             
-            if simulate_result.value.err.is_some() {
+            if preflight_pending {
+                match &simulate_result.value.err {
+                    None => {
+                        info!("preflight check passed in round {}", round_id);
+                        preflight_pending = false;
+                    }
+                    Some(TransactionError::InstructionError(_, InstructionError::Custom(REFINED_NO_SQUARE_DEPLOY))) => {
+                        info!("preflight check inconclusive in round {}: no qualifying square, retrying next round", round_id);
+                    }
+                    Some(err) => anyhow::bail!("preflight check failed: {}", decode_transaction_error(err)),
+                }
+            }
+
+            if let Some(err) = &simulate_result.value.err {
                 info!(
-                    "simulate transaction failed: {:?}",
-                    simulate_result.value.err
+                    "simulate transaction failed: {}",
+                    decode_transaction_error(err)
                 );
                 continue;
+            } else if args.dry_run {
+                if dry_run_round.is_none_or(|(id, _)| id != round_id) {
+                    let deployed = simulated_miner_deployed(&simulate_result.value).unwrap_or_default();
+                    let spend = deployed.iter().sum::<u64>();
+                    info!(
+                        "dry run: round {} would deploy {} SOL (amount {} SOL, ore_refined_rate {}, ORE {} / SOL {} USDC, {} slots left) on squares:\n{}",
+                        round_id,
+                        lamports_to_sol(spend),
+                        lamports_to_sol(deploy_amount),
+                        args.ore_refined_rate,
+                        ore_price,
+                        sol_price,
+                        slot_left,
+                        BoardGrid::from(deployed)
+                    );
+                    dry_run_round = Some((round_id, spend));
+                    dry_run_total += spend;
+                }
+                continue;
+            } else if !ramp.is_live() {
+                if !ramp.has_position(round_id) {
+                    if let Some(deployed) = simulated_miner_deployed(&simulate_result.value) {
+                        info!("observe-only: paper deploy in round {}", round_id);
+                        ramp.observe(round_id, deployed);
+                    }
+                }
+                continue;
             } else {
-                //send ixs by rpc
-                submit_transaction_with_ixs(&rpc, &payer, &ixs, units_consumed).await?;
+                if args.explain && explained_round_id != Some(round_id) {
+                    explained_round_id = Some(round_id);
+                    let round_deployed = round_mutex.lock().await.deployed;
+                    // the deploy's own simulation already has the squares, no second round trip
+                    match simulated_miner_deployed(&simulate_result.value) {
+                        Some(deployed) => {
+                            let mut guards = vec![Guard {
+                                name: "deploy window",
+                                detail: format!("{} slots left <= remaining_slots {}", slot_left, args.remaining_slots),
+                            }];
+                            if let Some(window) = commit_window {
+                                guards.push(Guard {
+                                    name: "commit window",
+                                    detail: format!("slot {} <= latest safe slot {}", clock.slot, window.latest_safe_slot),
+                                });
+                            }
+                            if args.strict {
+                                guards.push(Guard {
+                                    name: "strict band",
+                                    detail: format!("{} <= {} slots left, first submission this round", args.strict_min_slots, slot_left),
+                                });
+                            }
+                            guards.push(Guard {
+                                name: "pot",
+                                detail: format!(
+                                    "{} SOL >= min_pot_sol {}, max_pot_multiple {:?}",
+                                    lamports_to_sol(pot), args.min_pot_sol, args.max_pot_multiple
+                                ),
+                            });
+                            guards.push(Guard {
+                                name: "round budget",
+                                detail: format!("{:?} left", round_budget.remaining()),
+                            });
+                            guards.push(Guard {
+                                name: "simulation",
+                                detail: format!("ok, {} units", units_consumed),
+                            });
+                            let explanation = Explanation {
+                                round_id,
+                                slot: clock.slot,
+                                slot_left,
+                                ore_price,
+                                sol_price,
+                                ore_refined_rate: args.ore_refined_rate,
+                                deploy_amount,
+                                pot,
+                                pot_ema: pot_ema.value(),
+                                guards,
+                                squares: Explanation::squares_from(&round_deployed, &deployed),
+                            };
+                            info!("explain:\n{}", explanation);
+                            info!("explain json: {}", serde_json::to_string(&explanation)?);
+                        }
+                        None => info!("explain: simulation returned no miner state"),
+                    }
+                }
+
+                if args.deploy_jitter_ms > 0 {
+                    // never sleep past the round budget or the slots left, the slot band still wins
+                    let jitter = tokio::time::Duration::from_millis(rand::thread_rng().gen_range(0..=args.deploy_jitter_ms))
+                        .min(round_budget.remaining())
+                        .min(slot_time.time_for(slot_left.saturating_sub(1)));
+                    info!("deploy jitter: {:?}", jitter);
+                    tokio::time::sleep(jitter).await;
+                }
+
+                let stream_tip = match (&jito_tips, args.jito_tip_percentile) {
+                    (Some(tips), Some(percentile)) => tips.read().await.percentile(percentile),
+                    _ => None,
+                };
+                let tip = jito_tip(tip_controller.lock().await.tip(), stream_tip, args.jito_tip_max);
+                info!("round {}: jito tip {} lamports", round_id, tip);
+                let (blockhash, blockhash_slot) = match round_budget.spend(blockhash_cache.get(&send_rpc, clock.slot)).await {
+                    Ok(blockhash) => blockhash,
+                    Err(err) => {
+                        info!("get blockhash failed: {:?}", err);
+                        report_rpc_failure(&health, &notifier, &payer.pubkey(), "get blockhash").await;
+                        continue;
+                    }
+                };
+
+                //send ixs by rpc, optionally as the very same transaction jito gets
+                let jito_transaction = if args.shared_transaction && jito_enabled {
+                    match round_budget.spend(build_jito_transaction(&rpc, &payer, &ixs, units_consumed, tip, Some(blockhash), &lookup_tables).instrument(info_span!("sign", round_id))).await {
+                        Ok(transaction) => Some(transaction),
+                        Err(err) => {
+                            info!("build transaction failed: {:?}", err);
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                // a shared transaction pays through its tip, only plain rpc submissions escalate
+                let unit_price = match &jito_transaction {
+                    Some(_) => 0,
+                    None => {
+                        let (attempt, unit_price) = fee_escalation.next(round_id);
+                        info!("round {}: rpc submission {} at {} micro-lamports per CU", round_id, attempt, unit_price);
+                        unit_price
+                    }
+                };
+                let submit_result = match &jito_transaction {
+                    Some(transaction) => round_budget.spend(send_versioned_transaction(&send_rpc, transaction, args.skip_preflight).instrument(info_span!("submit", round_id))).await,
+                    None => round_budget.spend(submit_transaction_with_ixs(&send_rpc, &payer, &ixs, units_consumed, unit_price, args.skip_preflight, args.tx_version, Some(blockhash), &lookup_tables).instrument(info_span!("submit", round_id))).await,
+                };
+                let signature = match submit_result {
+                    Ok(signature) => signature,
+                    Err(err) => {
+                        info!("submit transaction failed: {:?}", err);
+                        report_rpc_failure(&health, &notifier, &payer.pubkey(), "submit").await;
+                        continue;
+                    }
+                };
+                if args.verify_deploys {
+                    if let Some(expected) = simulated_miner_deployed(&simulate_result.value) {
+                        intended_deploy = Some((round_id, expected));
+                    }
+                }
+                let squares = simulated_miner_deployed(&simulate_result.value)
+                    .map(|deployed| (0..25).filter(|i| deployed[*i] > 0).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                info!(
+                    round_id,
+                    slots_remaining = slot_left,
+                    squares = ?squares,
+                    amount_sol = lamports_to_sol(round_total),
+                    signature = %signature,
+                    "deploy submitted"
+                );
+                ipc.publish(&IpcEvent::Deploy {
+                    wallet: payer.pubkey().to_string(),
+                    round_id,
+                    slots_remaining: slot_left,
+                    amount: round_total,
+                    squares: squares.clone(),
+                    signature: signature.to_string(),
+                });
+                notifier.notify(format!(
+                    "{}: deployed {} SOL in round {} to squares {:?} ({} slots left)",
+                    payer.pubkey(), lamports_to_sol(round_total), round_id, squares, slot_left
+                ));
+                stats::record(stats.as_ref(), StatsEvent::Deploy {
+                    round_id,
+                    amount: round_total,
+                    channel: "rpc",
+                    signature: signature.to_string(),
+                    bundle_id: None,
+                }).await;
+                let program_fee = round_total * 5 / 1000;
+                let priority_fee = units_consumed * unit_price / 1_000_000;
+                // spend and fees are booked once the deploy lands, resubmissions
+                // in the same round fail on-chain or never land
+                session.record_deploy(round_id);
+                Metrics::inc(&metrics.deploys_submitted);
+                Metrics::set(&metrics.last_deploy_slots_remaining, slot_left);
+                save_session(&mut session, args.state_file.as_deref(), daily_spend.as_deref()).await;
+                if args.strict {
+                    deployed_round_id = Some(round_id);
+                    info!("strict mode: round {} submitted at slot {} (slot_left: {})", round_id, clock.slot, slot_left);
+                }
+
+                let confirmed_template = webhook.as_ref().map(|_| DeployConfirmed {
+                    schema_version: webhook::SCHEMA_VERSION,
+                    round_id,
+                    channel: if jito_transaction.is_some() { "shared" } else { "rpc" },
+                    signature: signature.to_string(),
+                    bundle_id: None,
+                    landed_slot: 0,
+                    amount: round_total,
+                    squares: simulated_miner_deployed(&simulate_result.value)
+                        .map(|deployed| DeployConfirmed::squares_from(&deployed))
+                        .unwrap_or_default(),
+                    program_fee,
+                    priority_fee,
+                    // a shared transaction carries the tip on both channels
+                    jito_tip: if jito_transaction.is_some() { tip } else { 0 },
+                    decision: Decision {
+                        slot: clock.slot,
+                        slot_left,
+                        ore_price,
+                        sol_price,
+                        ore_refined_rate: args.ore_refined_rate,
+                        remaining_slots: args.remaining_slots,
+                        pot,
+                    },
+                });
 
-                //send ixs by jito
-                req_id += 1;
+                // the transaction stays live for as long as its blockhash, which
+                // may have been fetched a few slots before this one
+                req_ids.mark_used(req_id, blockhash_slot);
+
+                // the jito copy goes out first, then a single poll follows every
+                // copy of the deploy and hands the outcome to whoever needs it
                 let rpc_clone = rpc.clone();
                 let payer_clone = payer.clone();
-                tokio::spawn(async move {
-                    let result = send_ix_use_jito(&rpc_clone, &payer_clone, &ixs,units_consumed).await;
+                let stats_clone = stats.clone();
+                let health_clone = health.clone();
+                let tip_controller_clone = tip_controller.clone();
+                let suppress_jito_after_rpc_ms = args.suppress_jito_after_rpc_ms;
+                let webhook_clone = webhook.clone();
+                let jito_regions_clone = jito_regions.clone();
+                let lookup_tables_clone = lookup_tables.clone();
+                let metrics_clone = metrics.clone();
+                let landing_latency = args.commit_window.then(|| landing_latency.clone());
+                let submitted_slot = clock.slot;
+                let shared = jito_transaction.is_some();
+                let daily_spend_clone = daily_spend.clone();
+                let landed_tx = landed_tx.clone();
+                in_flight.spawn(async move {
+                    // a shared transaction has a single signature for both channels
+                    let mut candidates = vec![(if shared { "shared" } else { "rpc" }, signature)];
+                    let mut jito_submission = None;
+                    if jito_enabled {
+                        match jito_suppression_reason(&rpc_clone, &signature, suppress_jito_after_rpc_ms).await {
+                            Some(reason) => info!("round {}: skip jito submission, {}", round_id, reason),
+                            None => {
+                                let result = match jito_transaction {
+                                    Some(transaction) => {
+                                        let signature = transaction.signatures[0];
+                                        send_bundle(vec![transaction], jito_regions_clone.as_deref()).await.map(|outcome| (signature, outcome))
+                                    }
+                                    None => send_ix_use_jito(&rpc_clone, &payer_clone, &ixs,units_consumed, tip, Some(blockhash), jito_regions_clone.as_deref(), &lookup_tables_clone).await,
+                                };
+                                let jito_ok = matches!(&result, Ok((_, outcome)) if outcome.is_ok());
+                                health_clone.lock().await.update(Subsystem::Jito, jito_ok);
+                                if let Ok((jito_signature, outcome)) = result {
+                                    info!("round {}: jito bundle {:?} ({})", round_id, outcome, jito_signature);
+                                    let bundle_id = outcome.bundle_id();
+                                    stats::record(stats_clone.as_ref(), StatsEvent::Deploy {
+                                        round_id,
+                                        amount: round_total,
+                                        channel: "jito",
+                                        signature: jito_signature.to_string(),
+                                        bundle_id: bundle_id.clone(),
+                                    }).await;
+                                    if !shared {
+                                        candidates.push(("jito", jito_signature));
+                                    }
+                                    jito_submission = Some(bundle_id);
+                                }
+                            }
+                        }
+                    }
+
+                    let timeout = tokio::time::Duration::from_secs(JITO_LANDING_TIMEOUT_SECS);
+                    let landing = match confirm_first_landing(&rpc_clone, &candidates, timeout).await {
+                        Ok(landing) => landing,
+                        Err(err) => {
+                            warn!("round {}: could not confirm deploy: {:?}", round_id, err);
+                            return;
+                        }
+                    };
+                    // jito drops a bundle once the rpc copy landed, so that and a
+                    // failed copy say nothing about the tip, only a deploy no copy
+                    // of which was seen is a miss
+                    if jito_submission.is_some() {
+                        match landing {
+                            Landing::Landed("jito", _) => tip_controller_clone.lock().await.observe(true),
+                            Landing::Unseen => tip_controller_clone.lock().await.observe(false),
+                            _ => {}
+                        }
+                    }
+                    let Landing::Landed(channel, slot) = landing else {
+                        match landing {
+                            Landing::Unseen => warn!("round {}: deploy not confirmed on any channel within {:?}", round_id, timeout),
+                            _ => info!("round {}: deploy failed on every channel", round_id),
+                        }
+                        return;
+                    };
+                    metrics_clone.landed(channel);
+                    info!(
+                        round_id,
+                        channel,
+                        slot,
+                        "round {}: deploy landed via {} in slot {} ({} slots after submission)",
+                        round_id, channel, slot, slot.saturating_sub(submitted_slot)
+                    );
+                    if let Some(landing_latency) = landing_latency {
+                        landing_latency.lock().await.observe(submitted_slot, slot);
+                    }
+                    let spent = round_total
+                        + program_fee
+                        + match channel {
+                            "rpc" => priority_fee,
+                            _ => tip,
+                        };
+                    if let Some(daily_spend) = daily_spend_clone {
+                        daily_spend.lock().await.record(spent);
+                    }
+                    let _ = landed_tx.send((round_id, spent));
+                    // the priority fee and the tip are only paid by the copy carrying them
+                    stats::record(stats_clone.as_ref(), StatsEvent::Fee {
+                        round_id,
+                        program_fee,
+                        priority_fee: if channel == "rpc" { priority_fee } else { 0 },
+                        jito_tip: if channel == "rpc" { 0 } else { tip },
+                    }).await;
+                    if let (Some(webhook), Some(event)) = (webhook_clone, confirmed_template) {
+                        let landed_signature = candidates
+                            .iter()
+                            .find(|(candidate, _)| *candidate == channel)
+                            .map_or(signature, |(_, signature)| *signature);
+                        let event = match channel {
+                            "jito" => DeployConfirmed {
+                                channel,
+                                signature: landed_signature.to_string(),
+                                bundle_id: jito_submission.flatten(),
+                                priority_fee: 0,
+                                jito_tip: tip,
+                                landed_slot: slot,
+                                ..event
+                            },
+                            _ => DeployConfirmed { landed_slot: slot, ..event },
+                        };
+                        if let Err(err) = webhook.post(&event).await {
+                            warn!("deploy webhook failed for {}: {:?}", landed_signature, err);
+                        }
+                    }
                 });
             }
         }
 
-        
+        if let Some(shadow) = shadow.as_mut().filter(|s| !s.has_position(round_id)) {
+            let shadow_ix = get_ore_refined_ix(
+                payer.pubkey(),
+                round_id,
+                ore_price,
+                sol_price,
+                deploy_amount,
+                args.remaining_slots,
+                shadow.ore_refined_rate,
+                req_id,
+            )?;
+            let shadow_ixs = checkpoint_ix.into_iter().chain([shadow_ix, claim_sol(payer.pubkey())]).collect::<Vec<_>>();
+            match simulate_deployed_squares(&rpc, &payer, &shadow_ixs).await {
+                Ok(Some(deployed)) => shadow.open(round_id, deployed),
+                Ok(None) => {}
+                Err(err) => info!("shadow simulation failed: {:?}", err),
+            }
+        }
     }
 
+    if !in_flight.is_empty() {
+        info!("waiting for {} in-flight deploys and claims", in_flight.len());
+        while in_flight.join_next().await.is_some() {}
+    }
+    if book_landed(&mut landed_rx, &mut balance_watch, &mut session, &mut summary, &mut pnl, &metrics) {
+        save_session(&mut session, args.state_file.as_deref(), daily_spend.as_deref()).await;
+    }
+    info!("session summary: {}", summary);
+    info!("profit and loss at ORE = {:.6} SOL:\n{}", ore_price / sol_price, pnl.table(ore_price / sol_price));
     Ok(())
 }
 
-async fn update_board_loop(
-    rpc_client: Arc<RpcClient>,
-    board: Arc<Mutex<Board>>,
-) -> anyhow::Result<()> {
-    tokio::spawn(async move {
-        loop {
-            let new_board = get_board(&rpc_client).await.unwrap();
+/// Books the deploys that landed since the last call in the spend ledgers,
+/// true if there were any.
+fn book_landed(
+    landed: &mut tokio::sync::mpsc::UnboundedReceiver<(u64, u64)>,
+    balance_watch: &mut Option<BalanceWatch>,
+    session: &mut SessionState,
+    summary: &mut RunSummary,
+    pnl: &mut PnlTracker,
+    metrics: &Metrics,
+) -> bool {
+    let mut booked = false;
+    while let Ok((round_id, spent)) = landed.try_recv() {
+        if let Some(balance_watch) = balance_watch.as_mut() {
+            balance_watch.record_spend(spent);
+        }
+        session.spent += spent;
+        summary.record_deploy(round_id, spent);
+        pnl.record_spend(spent);
+        Metrics::add(&metrics.spent, spent);
+        booked = true;
+    }
+    booked
+}
 
-            {
-                let mut board_guard = board.lock().await;
-                *board_guard = new_board;
-            }
+async fn save_session(session: &mut SessionState, path: Option<&str>, daily_spend: Option<&Mutex<DailySpend>>) {
+    let Some(path) = path else {
+        return;
+    };
+    if let Some(daily_spend) = daily_spend {
+        session.daily_spends = daily_spend.lock().await.spends();
+    }
+    if let Err(err) = session.save(path) {
+        warn!("failed to save session state to {}: {:?}", path, err);
+    }
+}
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
-    });
-    Ok(())
+/// Board, clock and round, polled once for every wallet.
+#[derive(Clone)]
+struct PolledState {
+    board: Arc<Mutex<Board>>,
+    clock: Arc<Mutex<Clock>>,
+    round: Arc<Mutex<Round>>,
 }
 
-async fn update_miner_loop(
-    rpc: Arc<RpcClient>,
-    payer: Arc<Keypair>,
+/// A wallet's share of the poll: its miner account, the health of its
+/// subsystems and a wake-up whenever the board or the slot moved.
+#[derive(Clone)]
+struct WalletState {
+    authority: Pubkey,
     miner: Arc<Mutex<Miner>>,
-) -> anyhow::Result<()> {
-    tokio::spawn(async move {
-        loop {
-            let new_miner = get_miner(&rpc, payer.pubkey()).await.unwrap();
+    health: Arc<Mutex<HealthState>>,
+    state_changed: Arc<Notify>,
+}
 
-            {
-                let mut miner_guard = miner.lock().await;
-                *miner_guard = new_miner;
-            }
+/// Jito tip stream and region latencies, followed once for every wallet.
+#[derive(Clone, Default)]
+struct JitoFeeds {
+    tips: Option<Arc<tokio::sync::RwLock<JitoTips>>>,
+    regions: Option<Arc<JitoRegionSelector>>,
+}
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+/// Starts the tip stream and the region probe the flags ask for. The tasks
+/// stop once the returned guard is dropped.
+async fn spawn_jito_feeds(args: &Args) -> anyhow::Result<(JitoFeeds, AbortOnDrop)> {
+    let mut feeds = JitoFeeds::default();
+    let mut tasks = vec![];
+    if args.no_jito {
+        return Ok((feeds, AbortOnDrop(tasks)));
+    }
+    if let Some(percentile) = args.jito_tip_percentile {
+        if JitoTips::default().percentile(percentile).is_none() {
+            anyhow::bail!("--jito-tip-percentile must be one of 25, 50, 75, 95, 99");
         }
-    });
-    Ok(())
+        let tips = Arc::new(tokio::sync::RwLock::new(JitoTips::default()));
+        tasks.push(subscribe_jito_tips(tips.clone()).await);
+        feeds.tips = Some(tips);
+    }
+    if let Some(secs) = args.jito_region_probe_secs {
+        let regions = Arc::new(JitoRegionSelector::default());
+        tasks.push(regions.clone().spawn_probe(tokio::time::Duration::from_secs(secs)));
+        feeds.regions = Some(regions);
+    }
+    Ok((feeds, AbortOnDrop(tasks)))
 }
 
-async fn update_clock_loop(rpc: Arc<RpcClient>, clock: Arc<Mutex<Clock>>) -> anyhow::Result<()> {
-    tokio::spawn(async move {
-        loop {
-            let new_clock = get_clock(&rpc).await.unwrap();
-
-            {
-                let mut clock_guard = clock.lock().await;
-                *clock_guard = new_clock;
+/// First read of the shared state and every wallet's miner.
+async fn initial_state(rpc: &RpcClient, payers: &[Arc<Keypair>], args: &Args) -> anyhow::Result<(PolledState, Vec<WalletState>)> {
+    let authorities = payers.iter().map(|payer| payer.pubkey()).collect::<Vec<_>>();
+    let state = get_all_state(rpc, &authorities, get_board(rpc).await?.round_id).await?;
+    let round = match state.round.filter(|round| round.id == state.board.round_id) {
+        Some(round) => round,
+        None => match get_round_opt(rpc, state.board.round_id).await? {
+            Some(round) => round,
+            // started right at a round transition, the poll loop picks up the new round
+            None => get_round(rpc, state.board.round_id.saturating_sub(1)).await?,
+        },
+    };
+    let mut wallets = Vec::with_capacity(authorities.len());
+    for (authority, miner) in authorities.into_iter().zip(state.miners) {
+        let miner = match miner {
+            Some(miner) => miner,
+            None if args.unregistered_miner == UnregisteredMiner::Register => {
+                info!("wallet {} is not registered as a miner yet, the first deploy registers it", authority);
+                Miner {
+                    authority,
+                    ..Miner::zeroed()
+                }
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
-    });
-    Ok(())
+            None => anyhow::bail!(
+                "wallet {} is not registered as a miner, pass --unregistered-miner register to let the first deploy register it",
+                authority
+            ),
+        };
+        wallets.push(WalletState {
+            authority,
+            miner: Arc::new(Mutex::new(miner)),
+            health: Arc::new(Mutex::new(HealthState::default())),
+            state_changed: Arc::new(Notify::new()),
+        });
+    }
+    let polled = PolledState {
+        board: Arc::new(Mutex::new(state.board)),
+        clock: Arc::new(Mutex::new(state.clock)),
+        round: Arc::new(Mutex::new(round)),
+    };
+    Ok((polled, wallets))
 }
 
+/// Round to follow after a poll, `None` keeps the current one. `polled` is
+/// the round the batched read asked for, `board_round` the board's round read
+/// on its own when `polled` lags the board (`Some(None)` while its PDA does
+/// not exist yet, `None` if it wasn't read).
+fn next_round(board_round_id: u64, polled: Option<Round>, board_round: Option<Option<Round>>) -> Option<Round> {
+    if let Some(round) = polled.filter(|round| round.id == board_round_id) {
+        return Some(round);
+    }
+    match board_round {
+        Some(Some(round)) => Some(round),
+        // keep following the previous round until the new PDA exists
+        Some(None) => polled,
+        None => None,
+    }
+}
 
-async fn update_round_loop(
+/// Polls board, clock, round and the miners of all wallets with one
+/// `getMultipleAccounts` call a second. The round is read on its own only
+/// when the board moved to a new round, and if the batched read fails the
+/// clock falls back to `get_clock_or_slot` so the slot keeps moving.
+fn update_state_loop(
     rpc: Arc<RpcClient>,
-    round: Arc<Mutex<Round>>,
-    board: Arc<Mutex<Board>>,
-) -> anyhow::Result<()> {
+    polled: PolledState,
+    wallets: Vec<WalletState>,
+    clock_retries: u32,
+) -> JoinHandle<()> {
+    let authorities = wallets.iter().map(|wallet| wallet.authority).collect::<Vec<_>>();
     tokio::spawn(async move {
+        let mut failures = 0u32;
         loop {
-            let round_id = {
-                board.lock().await.round_id
+            let round_id = polled.board.lock().await.round_id;
+            let changed = match get_all_state(&rpc, &authorities, round_id).await {
+                Ok(state) => {
+                    failures = 0;
+                    for wallet in &wallets {
+                        wallet.health.lock().await.update(Subsystem::Poll, true);
+                    }
+                    let board_round = if state.round.is_some_and(|round| round.id == state.board.round_id) {
+                        None
+                    } else {
+                        match get_round_opt(&rpc, state.board.round_id).await {
+                            Ok(round) => {
+                                if round.is_none() {
+                                    debug!("round {} not created yet, keeping round {}", state.board.round_id, round_id);
+                                }
+                                Some(round)
+                            }
+                            Err(err) => {
+                                warn!("failed to read round {}: {:?}", state.board.round_id, err);
+                                None
+                            }
+                        }
+                    };
+                    let new_round = next_round(state.board.round_id, state.round, board_round);
+                    for (wallet, miner) in wallets.iter().zip(state.miners) {
+                        if let Some(miner) = miner {
+                            *wallet.miner.lock().await = miner;
+                        }
+                    }
+                    if let Some(new_round) = new_round {
+                        *polled.round.lock().await = new_round;
+                    }
+                    let mut board_guard = polled.board.lock().await;
+                    let mut clock_guard = polled.clock.lock().await;
+                    let changed = board_guard.round_id != state.board.round_id
+                        || board_guard.end_slot != state.board.end_slot
+                        || clock_guard.slot != state.clock.slot;
+                    *board_guard = state.board;
+                    *clock_guard = state.clock;
+                    changed
+                }
+                Err(err) => {
+                    failures += 1;
+                    warn!("failed to read board, clock, miners and round ({} in a row): {:?}", failures, err);
+                    if failures >= POLL_FAILURE_THRESHOLD {
+                        for wallet in &wallets {
+                            wallet.health.lock().await.update(Subsystem::Poll, false);
+                        }
+                    }
+                    let previous = polled.clock.lock().await.clone();
+                    match get_clock_or_slot(&rpc, &previous, clock_retries).await {
+                        Ok(new_clock) => {
+                            let mut clock_guard = polled.clock.lock().await;
+                            let changed = clock_guard.slot != new_clock.slot;
+                            *clock_guard = new_clock;
+                            changed
+                        }
+                        Err(err) => {
+                            warn!("failed to read clock and slot: {:?}", err);
+                            false
+                        }
+                    }
+                }
             };
-            // 获取新的clock
-            let new_round = get_round(&rpc,round_id).await.unwrap();
+            if changed {
+                for wallet in &wallets {
+                    wallet.state_changed.notify_one();
+                }
+            }
+
+            // back off exponentially while the rpc keeps failing
+            let delay = tokio::time::Duration::from_secs(1 << failures.min(POLL_MAX_BACKOFF_SHIFT));
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
 
-            // 获取锁并更新数据
-            {
-                let mut clock_guard = round.lock().await;
-                *clock_guard = new_round;
+async fn update_blockhash_loop(
+    rpc: Arc<RpcClient>,
+    blockhash_cache: Arc<BlockhashCache>,
+    clock: Arc<Mutex<Clock>>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let handle = tokio::spawn(async move {
+        loop {
+            let slot = clock.lock().await.slot;
+            if let Err(err) = blockhash_cache.refresh(&rpc, slot).await {
+                warn!("failed to refresh blockhash: {:?}", err);
             }
 
-            // 添加延时避免过于频繁的请求
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
         }
     });
-    Ok(())
+    Ok(handle)
 }
 
 
-
-#[derive(Parser, Debug)]
-#[command(about, version)]
+#[derive(Parser, Clone, Debug)]
+#[command(about, version, subcommand_negates_reqs = true, args_override_self = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "JSON file of flag values, e.g. {\"rpc\": \"...\", \"per_round_deploy_amount\": 0.01}. Flags on the command line take precedence.",
+    )]
+    config: Option<String>,
 
     #[arg(
         long,
@@ -307,19 +1760,48 @@ struct Args {
     )]
     rpc: String,
 
+    #[arg(
+        long,
+        help = "Never use jito: no bundles, tip stream or region probe, deploys only go through the rpc. For clusters without a jito block engine such as devnet, where the ore programs must be deployed at the mainnet ids.",
+    )]
+    no_jito: bool,
+
+    #[arg(
+        long,
+        value_name = "RPC_URL",
+        help = "Separate RPC (e.g. a staked connection) that deploy transactions and their blockhashes go through, defaults to --rpc.",
+    )]
+    send_rpc: Option<String>,
+
     #[arg(
         long,
         value_name = "KEYPAIR_PATH",
         help = "Path to your Solana keypair file",
     )]
-    keypair: String,
+    keypair: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "File with one keypair path per line. Every wallet mines in parallel, alongside --keypair if given.",
+    )]
+    keypairs: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Watch-only: show the balances of this wallet without a keypair. Mining requires --keypair.",
+        conflicts_with_all = ["keypair", "keypairs"],
+    )]
+    pubkey: Option<String>,
 
     #[arg(
         long,
         value_name = "PER_ROUND_DEPLOY_AMOUNT",
         help = "The amount of SOL you expect to deploy in each round",
+        required_unless_present = "pubkey",
     )]
-    per_round_deploy_amount: f64,
+    per_round_deploy_amount: Option<f64>,
 
 
     #[arg(
@@ -337,6 +1819,771 @@ struct Args {
         help = "The refined rate of ORE you expect to get when deploying SOL. e.g. 1.3 means 1.3 ORE can be refined to 1 unclaimed ORE. The minimum is 1.1.",
         default_value = "1.3"
     )]
-    ore_refined_rate: f64
+    ore_refined_rate: f64,
+
 
-}
\ No newline at end of file
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Minimum interval between two price refreshes, the cached price is used in between.",
+        default_value = "10"
+    )]
+    price_refresh_secs: u64,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SOURCES",
+        value_delimiter = ',',
+        help = "Price sources to try in order, the first complete quote is used.",
+        default_value = "jupiter,coingecko"
+    )]
+    price_sources: Vec<PriceSourceKind>,
+
+
+    #[arg(
+        long,
+        help = "Strict timing: submit exactly once per round, only while the remaining slots are within [strict_min_slots, remaining_slots].",
+    )]
+    strict: bool,
+
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Lower bound of the strict mode slot band, no submission happens with fewer slots remaining.",
+        default_value = "2"
+    )]
+    strict_min_slots: u64,
+
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append deploy/claim/win/fee events to this JSONL file.",
+    )]
+    stats_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        help = "Serve Prometheus metrics (rounds, deploys, landings, balance, spend) on this port at /metrics.",
+    )]
+    metrics_port: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Address the metrics server listens on, use 0.0.0.0 to expose it beyond this host.",
+        default_value = "127.0.0.1"
+    )]
+    metrics_bind: std::net::IpAddr,
+
+
+    #[arg(
+        long,
+        help = "Claim unclaimed SOL and ORE (creating the ORE token account if needed) when shutting down with Ctrl+C.",
+    )]
+    claim_on_shutdown: bool,
+
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long to wait for a shutdown or auto claim to confirm.",
+        default_value = "30"
+    )]
+    claim_timeout_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Claim all rewards between rounds once the unclaimed SOL reaches this amount.",
+    )]
+    auto_claim_sol: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "ORE",
+        help = "Claim all rewards between rounds once the unclaimed ORE (including refined ORE) reaches this amount.",
+    )]
+    auto_claim_ore: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Exit cleanly once deploys were made in this many rounds and the last of them is over.",
+    )]
+    max_rounds: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Log the net profit and loss (rewards at the current ORE price minus spend) every N rounds. The full table is printed on shutdown either way.",
+    )]
+    pnl_every_rounds: Option<u64>,
+
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Time budget shared by price fetch, simulation and submission once the deploy window of a round opens.",
+        default_value = "6000"
+    )]
+    round_budget_ms: u64,
+
+
+    #[arg(
+        long,
+        value_name = "ORE_REFINED_RATE",
+        help = "Evaluate a second ore_refined_rate each round without submitting anything, and keep a paper ledger of what it would have deployed and won.",
+    )]
+    shadow_ore_refined_rate: Option<f64>,
+
+
+    #[arg(
+        long,
+        value_name = "MULTIPLE",
+        help = "Skip the round if its pot is more than this multiple of the moving average of recent pots.",
+    )]
+    max_pot_multiple: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Rounds the pot moving average must observe before max_pot_multiple trusts it.",
+        default_value = "5"
+    )]
+    pot_ema_warmup_rounds: u64,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Static pot used by max_pot_multiple while the moving average is warming up.",
+    )]
+    pot_reference_sol: Option<f64>,
+
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Skip the round if its pot is smaller than this amount of SOL.",
+        default_value = "0"
+    )]
+    min_pot_sol: f64,
+
+
+    #[arg(
+        long,
+        value_name = "BOOL",
+        help = "Skip the RPC preflight check when sending deploy transactions. Set to false to catch errors before paying fees, at the cost of some latency.",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    skip_preflight: bool,
+
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Warn once when the wallet SOL balance drops below this amount.",
+    )]
+    min_balance_alert_sol: Option<f64>,
+
+    #[arg(
+        long,
+        help = "Start mining even if the wallet can't cover a few rounds at the worst-case cost.",
+    )]
+    ignore_balance_check: bool,
+
+
+    #[arg(
+        long,
+        help = "Send the jito transaction (tip included) through the rpc as well, so both channels share one signature.",
+    )]
+    shared_transaction: bool,
+
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        help = "How per_round_deploy_amount is converted to lamports.",
+        default_value = "floor"
+    )]
+    amount_rounding: AmountRounding,
+
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Minimum deploy amount in lamports.",
+        default_value = "0"
+    )]
+    min_deploy_lamports: u64,
+
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ACTION",
+        help = "What to do when the deploy amount is below min_deploy_lamports.",
+        default_value = "error"
+    )]
+    below_min_deploy: BelowMinDeploy,
+
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VERSION",
+        help = "Transaction version used for rpc submission, use legacy for RPCs that don't accept v0 transactions.",
+        default_value = "v0"
+    )]
+    tx_version: TxVersion,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Address lookup table to compile deploy transactions against, can be given more than once. Needs v0 transactions.",
+    )]
+    lookup_table: Vec<String>,
+
+
+    #[arg(
+        long,
+        help = "Simulate the full deploy in the first deploy window and exit with the decoded error if it fails.",
+    )]
+    preflight_check: bool,
+
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Wait a random time up to this value before polling starts, to de-synchronize several instances.",
+        default_value = "0"
+    )]
+    startup_jitter_ms: u64,
+
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Wait a random time up to this value before each submission. Capped by the round budget and the time left before the last slot.",
+        default_value = "0"
+    )]
+    deploy_jitter_ms: u64,
+
+
+    #[arg(
+        long,
+        help = "Once per round, print the full reasoning behind the deploy (inputs, guards and the squares the program picks) as text and JSON.",
+    )]
+    explain: bool,
+
+
+    #[arg(
+        long,
+        value_name = "SQUARES",
+        help = "Defer the deploy while fewer than this many squares have deployments, the refined program can't tell empty squares apart.",
+        default_value = "0"
+    )]
+    min_populated_squares: usize,
+
+
+    #[arg(
+        long,
+        value_name = "RATE",
+        help = "Upper bound for ore_refined_rate, larger values are rejected at startup.",
+        default_value = "3.0"
+    )]
+    max_ore_refined_rate: f64,
+
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        value_delimiter = ',',
+        help = "Skip rounds in which any of these wallets has already deployed.",
+    )]
+    avoid_miners: Vec<String>,
+
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Also avoid the COUNT wallets that were top miner most often in the board history.",
+        default_value = "0"
+    )]
+    avoid_top_miners: usize,
+
+
+    #[arg(
+        long,
+        help = "After each round, check that the miner account holds the position the simulation of our deploy produced.",
+    )]
+    verify_deploys: bool,
+
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append one CSV row per round with the board state seen at decision time and the winning square, for offline analysis.",
+    )]
+    feature_log: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Jito tip per deploy, and the floor of the adaptive tip.",
+        default_value = "5000"
+    )]
+    jito_tip_min: u64,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Ceiling of the adaptive jito tip.",
+        default_value = "100000"
+    )]
+    jito_tip_max: u64,
+
+    #[arg(
+        long,
+        help = "Raise the jito tip when bundles stop landing and lower it again once they land, between --jito-tip-min and --jito-tip-max.",
+    )]
+    adaptive_tip: bool,
+
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        help = "Multiply the compute unit price by this on every rpc resubmission within a round, up to --max-cu-price. 1 keeps it fixed.",
+        default_value = "1.0"
+    )]
+    cu_price_escalation: f64,
+
+    #[arg(
+        long,
+        value_name = "MICRO_LAMPORTS",
+        help = "Ceiling of the escalated compute unit price.",
+        default_value = "200000"
+    )]
+    max_cu_price: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Hold the jito copy of a deploy for this long and skip it (saving the tip) if the rpc transaction confirmed meanwhile.",
+    )]
+    suppress_jito_after_rpc_ms: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Retries when reading the clock sysvar fails, after that the slot comes from getSlot.",
+        default_value = "2"
+    )]
+    clock_retries: u32,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Slot time assumed until it has been measured from the observed slot progression.",
+        default_value = "400"
+    )]
+    slot_time_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "POST a versioned JSON record (round, squares, fees, landing slot, decision inputs) to this url for every confirmed deploy.",
+    )]
+    deploy_webhook: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "Telegram bot token for deploy, claim and error alerts, defaults to TELEGRAM_BOT_TOKEN. Alerts are off unless a chat id is set too.",
+    )]
+    telegram_bot_token: Option<Secret>,
+
+    #[arg(
+        long,
+        value_name = "CHAT_ID",
+        help = "Telegram chat the alerts go to, defaults to TELEGRAM_CHAT_ID.",
+    )]
+    telegram_chat_id: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Stream round snapshots and deploys as newline-delimited JSON on this UNIX socket, for a dashboard process. Updates are dropped while nothing is connected.",
+    )]
+    ipc_socket: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Refresh the cached blockhash before submitting if it was fetched more than this many slots ago.",
+        default_value = "60"
+    )]
+    max_blockhash_age_slots: u64,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        help = "When to prepend the checkpoint instruction: always, or only when-needed (the miner has an un-checkpointed round).",
+        default_value = "when-needed"
+    )]
+    checkpoint_mode: CheckpointMode,
+
+    #[arg(
+        long,
+        help = "Only submit while the slot is before end_slot minus the measured landing latency and --landing-margin-slots.",
+    )]
+    commit_window: bool,
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Safety margin of the commit window on top of the landing latency.",
+        default_value = "1"
+    )]
+    landing_margin_slots: u64,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Paper trade the first ROUNDS rounds at the live ore_refined_rate before deploying for real.",
+        default_value = "0"
+    )]
+    observe_rounds: u64,
+
+    #[arg(
+        long,
+        value_name = "RATE",
+        help = "Paper win rate (0-1) the observe rounds must reach to go live.",
+        default_value = "0"
+    )]
+    go_live_min_win_rate: f64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "After the observe rounds, only go live once this file exists (manual confirmation).",
+    )]
+    go_live_confirm_file: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ACTION",
+        help = "What to do when the wallet is not registered as a miner: error, or register it with the first deploy.",
+        default_value = "error"
+    )]
+    unregistered_miner: UnregisteredMiner,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Sit out this many rounds after a checkpoint realizes a losing round.",
+        default_value = "0"
+    )]
+    loss_cooldown_rounds: u64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save the session state (moving averages, jito tip, cooldowns, played rounds, spend and earnings) here every round and deploy, and restore it on startup.",
+    )]
+    state_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Ignore a saved session state older than this.",
+        default_value = "3600"
+    )]
+    state_max_age_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "req_id values to cycle through (at most 256). A req_id is not reused until transactions carrying it can no longer land (150 slots).",
+        default_value = "100"
+    )]
+    req_id_space: u16,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Inside the deploy window, hold off until the pot reaches this many SOL or the deadline below is reached.",
+    )]
+    wait_for_pot_sol: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Deploy regardless of the pot once this few slots are left (or the commit window closes).",
+        default_value = "2"
+    )]
+    wait_for_pot_deadline_slots: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Probe the latency of every jito block engine this often and send bundles to the fastest one only instead of to all of them.",
+    )]
+    jito_region_probe_secs: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Build and simulate every deploy but never submit it, log what would have been deployed and the SOL it would have spent.",
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "PERCENTILE",
+        help = "Follow the jito tip stream and tip at this landed-tip percentile (25, 50, 75, 95 or 99), never below the configured tip nor above --jito-tip-max.",
+    )]
+    jito_tip_percentile: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Hard ceiling on the SOL deployed across all squares in one round, checked against the simulated deploy.",
+    )]
+    max_total_deploy_per_round_sol: Option<f64>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ACTION",
+        help = "What to do when a deploy exceeds max_total_deploy_per_round_sol: scale the amount down to fit, or skip the round.",
+        default_value = "scale"
+    )]
+    total_cap_action: TotalCapAction,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Stop deploying once this much SOL (deploys plus program and priority fees) was spent in the last 24 hours.",
+    )]
+    max_daily_spend_sol: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "UNITS",
+        help = "Lower bound of the compute unit limit derived from simulation.",
+        default_value = "200000"
+    )]
+    min_compute_units: u64,
+
+    #[arg(
+        long,
+        value_name = "UNITS",
+        help = "Upper bound of the compute unit limit derived from simulation, guards against simulation outliers.",
+        default_value = "600000"
+    )]
+    max_compute_units: u64,
+
+    #[arg(
+        long,
+        value_name = "USDC",
+        help = "Skip rounds while the ORE price is below this.",
+    )]
+    min_ore_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "USDC",
+        help = "Skip rounds while the ORE price is above this.",
+    )]
+    max_ore_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "USDC",
+        help = "Skip rounds while the SOL price is below this.",
+    )]
+    min_sol_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "USDC",
+        help = "Skip rounds while the SOL price is above this.",
+    )]
+    max_sol_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a chrome trace (chrome://tracing, Perfetto) of the deploy path spans to this file. Needs a build with --features profile.",
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Log output: human-readable lines, or one JSON object per event for log aggregators.",
+        default_value = "pretty"
+    )]
+    log_format: LogFormat,
+
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum Command {
+    /// Write the board, round, treasury, config and clock accounts to a JSON snapshot.
+    DumpRound {
+        /// Round to capture, defaults to the current one.
+        id: Option<u64>,
+
+        #[arg(long, value_name = "PATH", help = "Output file, defaults to round-<id>.json.")]
+        out: Option<String>,
+    },
+
+    /// Print, per square, up to which deploy amount the expected value stays positive.
+    BreakEven {
+        /// Round to evaluate, defaults to the current one.
+        id: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with = "id",
+            help = "Evaluate a round written by dump-round instead of reading it from the rpc. Prices are still the current ones.",
+        )]
+        snapshot: Option<String>,
+    },
+
+    /// Fetch the round accounts in [from, to] into the on-disk round cache.
+    FetchRounds {
+        from: u64,
+
+        to: u64,
+
+        #[arg(long, value_name = "N", help = "Maximum number of concurrent RPC requests.", default_value = "8")]
+        concurrency: usize,
+
+        #[arg(long, value_name = "DIR", help = "Directory finished rounds are cached in.", default_value = "rounds")]
+        cache_dir: String,
+    },
+
+    /// Print the live deployment grid of the current round until Ctrl+C, without mining.
+    Watch {
+        #[arg(long, value_name = "MS", help = "Refresh interval.", default_value = "1000")]
+        interval_ms: u64,
+    },
+
+    /// Print a heatmap of winning squares and payout stats over recent rounds.
+    History {
+        #[arg(long, value_name = "N", help = "Number of most recent rounds to analyze.", default_value = "100")]
+        rounds: usize,
+    },
+}
+
+/// `miner.round_id` is the last round the miner deployed in and
+/// `miner.checkpoint_id` the last round whose rewards were settled into it.
+/// Deploying into a new round requires `checkpoint_id == round_id`, so a
+/// checkpoint is only needed while `checkpoint_id < round_id`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CheckpointMode {
+    Always,
+    WhenNeeded,
+}
+
+/// The refined program registers the miner account on the wallet's first
+/// deploy, so `register` simply starts from an empty miner.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum UnregisteredMiner {
+    Error,
+    Register,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TotalCapAction {
+    Scale,
+    Skip,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum BelowMinDeploy {
+    Error,
+    Bump,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        let base = ["ore_refined", "--rpc", "http://127.0.0.1:8899", "--per-round-deploy-amount", "0.01"];
+        Args::try_parse_from(base.iter().chain(flags).copied()).unwrap()
+    }
+
+    #[test]
+    fn worst_case_round_cost_leaves_out_the_tip_without_jito() {
+        let deploy_amount = 10_000_000;
+        let with_jito = worst_case_round_cost(&args(&["--jito-tip-max", "70000"]), deploy_amount);
+        let without_jito = worst_case_round_cost(&args(&["--jito-tip-max", "70000", "--no-jito"]), deploy_amount);
+        assert_eq!(with_jito - without_jito, 70_000);
+    }
+
+    #[test]
+    fn worst_case_round_cost_respects_the_total_cap() {
+        let args = args(&["--max-total-deploy-per-round-sol", "0.1", "--no-jito"]);
+        let cost = worst_case_round_cost(&args, 10_000_000);
+        let deployed = 100_000_000;
+        assert!(cost >= deployed + deployed * 5 / 1000 && cost < 2 * deployed, "{}", cost);
+    }
+
+    #[test]
+    fn stream_tip_raises_the_tip_up_to_the_max() {
+        assert_eq!(jito_tip(10_000, None, 50_000), 10_000);
+        assert_eq!(jito_tip(10_000, Some(5_000), 50_000), 10_000);
+        assert_eq!(jito_tip(10_000, Some(30_000), 50_000), 30_000);
+        assert_eq!(jito_tip(10_000, Some(900_000), 50_000), 50_000);
+    }
+
+    fn round(id: u64) -> Round {
+        Round {
+            id,
+            ..Round::zeroed()
+        }
+    }
+
+    #[test]
+    fn next_round_takes_the_polled_round_when_current() {
+        let next = next_round(10, Some(round(10)), None);
+        assert_eq!(next.map(|round| round.id), Some(10));
+    }
+
+    #[test]
+    fn next_round_keeps_the_previous_round_while_the_pda_lags() {
+        // the board moved to round 11 before its PDA was created
+        let next = next_round(11, Some(round(10)), Some(None));
+        assert_eq!(next.map(|round| round.id), Some(10));
+        // and switches once the PDA can be read
+        let next = next_round(11, Some(round(10)), Some(Some(round(11))));
+        assert_eq!(next.map(|round| round.id), Some(11));
+    }
+
+    #[test]
+    fn next_round_keeps_the_current_round_when_the_read_failed() {
+        assert!(next_round(11, Some(round(10)), None).is_none());
+        assert!(next_round(11, None, Some(None)).is_none());
+    }
+
+    #[test]
+    fn ore_refined_rate_bounds() {
+        assert!(check_ore_refined_rate("ore_refined_rate", 1.3, 2.0).is_ok());
+        assert!(check_ore_refined_rate("ore_refined_rate", MIN_ORE_REFINED_RATE, 2.0).is_ok());
+        assert!(check_ore_refined_rate("ore_refined_rate", 2.0, 2.0).is_ok());
+        assert!(check_ore_refined_rate("ore_refined_rate", 1.0, 2.0).is_err());
+        let err = check_ore_refined_rate("shadow_ore_refined_rate", 2.5, 2.0).unwrap_err();
+        assert!(err.to_string().starts_with("shadow_ore_refined_rate 2.5"), "{}", err);
+    }
+}
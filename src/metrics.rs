@@ -0,0 +1,191 @@
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use solana_sdk::pubkey::Pubkey;
+use crate::health::{HealthState, Subsystem};
+use crate::jito::BLOCK_ENGINE_URLS;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Counters and gauges of one wallet's mining loop, rendered in the
+/// Prometheus text format by `serve`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    wallet: Pubkey,
+    pub rounds_seen: AtomicU64,
+    pub deploys_submitted: AtomicU64,
+    pub deploys_confirmed: AtomicU64,
+    pub landed_rpc: AtomicU64,
+    pub landed_jito: AtomicU64,
+    pub landed_shared: AtomicU64,
+    pub sol_balance: AtomicU64,
+    pub unclaimed_ore: AtomicU64,
+    pub last_deploy_slots_remaining: AtomicU64,
+    pub spent: AtomicU64,
+    /// 1 while the subsystem is degraded, indexed by `Subsystem`.
+    pub health_degraded: [AtomicU64; 5],
+    /// Index into `BLOCK_ENGINE_URLS` plus one, 0 until a region was measured.
+    jito_best_region: AtomicU64,
+    jito_best_region_latency_ms: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new(wallet: Pubkey) -> Self {
+        Self {
+            wallet,
+            ..Default::default()
+        }
+    }
+
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn set(gauge: &AtomicU64, value: u64) {
+        gauge.store(value, Ordering::Relaxed);
+    }
+
+    pub fn landed(&self, channel: &str) {
+        Self::inc(&self.deploys_confirmed);
+        match channel {
+            "rpc" => Self::inc(&self.landed_rpc),
+            "jito" => Self::inc(&self.landed_jito),
+            _ => Self::inc(&self.landed_shared),
+        }
+    }
+
+    /// Mirrors the health of the wallet's subsystems into the gauges.
+    pub fn observe_health(&self, health: &HealthState) {
+        for subsystem in Subsystem::ALL {
+            Self::set(&self.health_degraded[subsystem as usize], health.is_degraded(subsystem) as u64);
+        }
+    }
+
+    /// Records the fastest jito region as reported by `JitoRegionSelector::best`.
+    pub fn observe_jito_region(&self, (index, ms): (usize, f64)) {
+        Self::set(&self.jito_best_region, index as u64 + 1);
+        Self::set(&self.jito_best_region_latency_ms, ms.round() as u64);
+    }
+
+    fn render(&self, out: &mut String) {
+        let wallet = self.wallet;
+        let mut line = |name: &str, value: &AtomicU64, labels: &str| {
+            let _ = writeln!(out, "ore_refined_{}{{wallet=\"{}\"{}}} {}", name, wallet, labels, value.load(Ordering::Relaxed));
+        };
+        line("rounds_seen_total", &self.rounds_seen, "");
+        line("deploys_submitted_total", &self.deploys_submitted, "");
+        line("deploys_confirmed_total", &self.deploys_confirmed, "");
+        line("deploys_landed_total", &self.landed_rpc, ",channel=\"rpc\"");
+        line("deploys_landed_total", &self.landed_jito, ",channel=\"jito\"");
+        line("deploys_landed_total", &self.landed_shared, ",channel=\"shared\"");
+        line("sol_balance_lamports", &self.sol_balance, "");
+        // mined plus refined, what a claim would pay out
+        line("unclaimed_ore_grams", &self.unclaimed_ore, "");
+        line("last_deploy_slots_remaining", &self.last_deploy_slots_remaining, "");
+        line("spent_lamports_total", &self.spent, "");
+        for subsystem in Subsystem::ALL {
+            let labels = format!(",subsystem=\"{}\"", subsystem.name());
+            line("health_degraded", &self.health_degraded[subsystem as usize], &labels);
+        }
+        let best_region = self.jito_best_region.load(Ordering::Relaxed) as usize;
+        if let Some(url) = best_region.checked_sub(1).and_then(|index| BLOCK_ENGINE_URLS.get(index)) {
+            let labels = format!(",region=\"{}\"", url);
+            line("jito_best_region_latency_ms", &self.jito_best_region_latency_ms, &labels);
+        }
+    }
+}
+
+/// Serves the metrics of all `wallets` on `GET /metrics` at `addr`.
+pub async fn serve(addr: SocketAddr, wallets: Vec<Arc<Metrics>>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("metrics on http://{}/metrics", addr);
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    warn!("metrics accept failed: {:?}", err);
+                    continue;
+                }
+            };
+            let wallets = wallets.clone();
+            tokio::spawn(async move {
+                // the request itself doesn't matter, every path gets the metrics
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let mut body = String::new();
+                for wallet in &wallets {
+                    wallet.render(&mut body);
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_labels_every_line_with_the_wallet() {
+        let metrics = Metrics::new(Pubkey::new_unique());
+        Metrics::add(&metrics.spent, 1_500);
+        metrics.landed("jito");
+        let mut out = String::new();
+        metrics.render(&mut out);
+        let wallet = format!("wallet=\"{}\"", metrics.wallet);
+        assert!(out.lines().all(|line| line.contains(&wallet)));
+        assert!(out.contains(&format!("ore_refined_spent_lamports_total{{{}}} 1500", wallet)));
+        assert!(out.contains(&format!("ore_refined_deploys_landed_total{{{},channel=\"jito\"}} 1", wallet)));
+        assert!(out.contains(&format!("ore_refined_deploys_confirmed_total{{{}}} 1", wallet)));
+    }
+
+    #[test]
+    fn health_gauges_follow_the_health_state() {
+        let metrics = Metrics::new(Pubkey::new_unique());
+        let mut health = HealthState::default();
+        health.update(Subsystem::Jito, false);
+        metrics.observe_health(&health);
+        let mut out = String::new();
+        metrics.render(&mut out);
+        let gauge = |subsystem: &str| {
+            out.lines()
+                .find(|line| line.starts_with("ore_refined_health_degraded") && line.contains(&format!("subsystem=\"{}\"", subsystem)))
+                .and_then(|line| line.rsplit(' ').next())
+                .map(str::to_string)
+        };
+        assert_eq!(gauge("jito").as_deref(), Some("1"));
+        assert_eq!(gauge("rpc").as_deref(), Some("0"));
+
+        health.update(Subsystem::Jito, true);
+        metrics.observe_health(&health);
+        assert_eq!(metrics.health_degraded[Subsystem::Jito as usize].load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn best_jito_region_only_rendered_once_measured() {
+        let metrics = Metrics::new(Pubkey::new_unique());
+        let mut out = String::new();
+        metrics.render(&mut out);
+        assert!(!out.contains("jito_best_region"));
+
+        metrics.observe_jito_region((2, 41.6));
+        out.clear();
+        metrics.render(&mut out);
+        let expected = format!("region=\"{}\"}} 42", BLOCK_ENGINE_URLS[2]);
+        assert!(out.lines().any(|line| line.starts_with("ore_refined_jito_best_region_latency_ms") && line.ends_with(&expected)), "{}", out);
+    }
+}
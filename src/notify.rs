@@ -0,0 +1,112 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use serde_json::json;
+use solana_client::client_error::reqwest;
+use tracing::warn;
+
+/// A credential taken from the command line. Its `Debug` never prints it, so
+/// the `Args` dump at startup is safe to log.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(self) -> String {
+        self.0
+    }
+}
+
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"<redacted>\"")
+    }
+}
+
+pub struct TelegramNotifier {
+    bot_token: String,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+impl TelegramNotifier {
+    pub fn new(bot_token: String, chat_id: String) -> Self {
+        Self {
+            bot_token,
+            chat_id,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Errors are stripped of the request url, it carries the bot token.
+    pub async fn send(&self, msg: &str) -> anyhow::Result<()> {
+        self.client
+            .post(format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token))
+            .header("Content-Type", "application/json")
+            .body(json!({ "chat_id": self.chat_id, "text": msg }).to_string())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| err.without_url())?;
+        Ok(())
+    }
+}
+
+/// Push alerts for operators. Does nothing unless telegram is configured, and
+/// a failed alert is only logged, never surfaced to the mining loop.
+#[derive(Clone, Default)]
+pub struct Notifier(Option<Arc<TelegramNotifier>>);
+
+impl Notifier {
+    /// Uses the given token and chat id, falling back to the
+    /// `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID` environment variables.
+    pub fn telegram(bot_token: Option<Secret>, chat_id: Option<String>) -> Self {
+        let bot_token = bot_token.map(Secret::expose).or_else(|| std::env::var("TELEGRAM_BOT_TOKEN").ok());
+        let chat_id = chat_id.or_else(|| std::env::var("TELEGRAM_CHAT_ID").ok());
+        match (bot_token, chat_id) {
+            (Some(bot_token), Some(chat_id)) => Self(Some(Arc::new(TelegramNotifier::new(bot_token, chat_id)))),
+            _ => Self(None),
+        }
+    }
+
+    /// Sends `msg` in the background.
+    pub fn notify(&self, msg: String) {
+        if self.0.is_none() {
+            return;
+        }
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            notifier.send(&msg).await;
+        });
+    }
+
+    /// Sends `msg` and waits for it, for alerts right before the process exits.
+    pub async fn send(&self, msg: &str) {
+        let Some(telegram) = &self.0 else {
+            return;
+        };
+        if let Err(err) = telegram.send(msg).await {
+            warn!("telegram notification failed: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_debug_is_redacted() {
+        let token: Secret = "123456:ABC-secret".parse().unwrap();
+        assert_eq!(format!("{:?}", Some(token.clone())), "Some(\"<redacted>\")");
+        assert_eq!(token.expose(), "123456:ABC-secret");
+    }
+}
@@ -1,20 +1,10 @@
 use std::collections::HashSet;
 use std::fs;
-use std::rc::Rc;
-use std::sync::Arc;
 use ore_api::consts::MINT_ADDRESS;
 // use ore_api::prelude::{block_pda, config_pda, market_pda, miner_pda, vault_address, Block, Config, Market, Miner, SwapDirection, SwapPrecision};
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::pubkey::Pubkey;
 use solana_program::{system_program, sysvar};
-use anchor_client::{
-    solana_client::rpc_client::RpcClient,
-    solana_sdk::{
-        commitment_config::CommitmentConfig, native_token::LAMPORTS_PER_SOL, signature::Keypair,
-        signer::Signer,
-    },
-    Client, Cluster,
-};
 use anchor_lang::prelude::*;
 use log::info;
 use ore_api::prelude::{automation_pda, board_pda, miner_pda, treasury_pda};
@@ -37,22 +27,8 @@ pub fn get_ore_refined_ix(
 
     info!("deploy_amount: {:?}",deploy_amount);
 
-
-    let url = Cluster::Custom(
-        "http://localhost:8899".to_string(),
-        "ws://127.0.0.1:8900".to_string(),
-    );
-
-    let payer = Arc::new(Keypair::new());
-    let program_client = Client::new(url.clone(), payer.clone());
-    // Create program client
-    let provider = Client::new_with_options(
-        Cluster::Localnet,
-        Rc::new(payer),
-        CommitmentConfig::confirmed(),
-    );
-    let program = provider.program(ore_por_program::ID)?;
-
+    // same metas and data anchor's `program.request().instructions()` produces,
+    // without standing up a client and provider just to serialize them
     let accounts = accounts::Refined {
         signer,
         authority: signer,
@@ -65,23 +41,59 @@ pub fn get_ore_refined_ix(
         ore_program: pubkey!("oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv"),
         fee: pubkey!("Feei2iwqp9Adcyte1F5XnKzGTFL1VDg4VyiypvoeiJyJ")
     };
+    let args = args::Refined {
+        ore_price,
+        sol_price,
+        amount: deploy_amount,
+        remaining_slots,
+        ore_refined_rate,
+        req_id
+    };
 
+    Ok(Instruction {
+        program_id: ore_por_program::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    })
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    /// What anchor's `program.request().instructions()` produced for the
+    /// refined instruction: the IDL discriminator, then the borsh args.
+    fn anchor_encoding(ore_price: f64, sol_price: f64, amount: u64, remaining_slots: u8, ore_refined_rate: f64, req_id: u8) -> Vec<u8> {
+        let mut data = vec![63, 24, 160, 231, 240, 209, 92, 51];
+        data.extend(ore_price.to_le_bytes());
+        data.extend(sol_price.to_le_bytes());
+        data.extend(amount.to_le_bytes());
+        data.push(remaining_slots);
+        data.extend(ore_refined_rate.to_le_bytes());
+        data.push(req_id);
+        data
+    }
 
-    let swap_ix = program.request()
-        .accounts(accounts)
-        // Remaining accounts
-        .args(args::Refined{
-            ore_price,
-            sol_price,
-            amount: deploy_amount,
-            remaining_slots,
-            ore_refined_rate,
-            req_id
-        })
-        .instructions()?.remove(0);
-
-
-    Ok(swap_ix)
-}
\ No newline at end of file
+    #[test]
+    fn refined_ix_matches_the_anchor_client_encoding() {
+        let signer = Pubkey::new_unique();
+        let ix = get_ore_refined_ix(signer, 77, 412.5, 187.25, 10_000_000, 12, 1.3, 9).unwrap();
+        assert_eq!(ix.program_id, pubkey!("HZJfY7oVkbWxsmMQX5ipqahMnC74H72UYaBXJ8an2PoR"));
+        assert_eq!(ix.data, anchor_encoding(412.5, 187.25, 10_000_000, 12, 1.3, 9));
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(signer, false),
+                AccountMeta::new(automation_pda(signer).0, false),
+                AccountMeta::new(board_pda().0, false),
+                AccountMeta::new(miner_pda(signer).0, false),
+                AccountMeta::new(round_pda(77).0, false),
+                AccountMeta::new(treasury_pda().0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(pubkey!("oreV3EG1i9BEgiAJ8b177Z2S2rMarzak4NMv1kULvWv"), false),
+                AccountMeta::new(pubkey!("Feei2iwqp9Adcyte1F5XnKzGTFL1VDg4VyiypvoeiJyJ"), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Write as _;
+use std::time::Instant;
+use ore_api::prelude::{Miner, TOKEN_DECIMALS};
+use solana_sdk::native_token::lamports_to_sol;
+use spl_token::amount_to_ui_amount;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Pnl {
+    pub spent: u64,
+    pub won_sol: u64,
+    /// Mined plus refined ORE.
+    pub won_ore: u64,
+}
+
+impl Pnl {
+    /// Net result in SOL, valuing ORE at `ore_in_sol` SOL each.
+    pub fn net_sol(&self, ore_in_sol: f64) -> f64 {
+        lamports_to_sol(self.won_sol) + amount_to_ui_amount(self.won_ore, TOKEN_DECIMALS) * ore_in_sol
+            - lamports_to_sol(self.spent)
+    }
+}
+
+/// Spend against rewards, bucketed per hour since the start of the run.
+pub struct PnlTracker {
+    started: Instant,
+    hours: Vec<Pnl>,
+}
+
+impl PnlTracker {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            hours: vec![Pnl::default()],
+        }
+    }
+
+    fn current(&mut self) -> &mut Pnl {
+        let hour = (self.started.elapsed().as_secs() / 3600) as usize;
+        if self.hours.len() <= hour {
+            self.hours.resize(hour + 1, Pnl::default());
+        }
+        &mut self.hours[hour]
+    }
+
+    pub fn record_spend(&mut self, lamports: u64) {
+        self.current().spent += lamports;
+    }
+
+    /// Books the rewards that appeared between two miner snapshots. Wins come
+    /// from the lifetime counters, refined ORE from its growth (a claim resets it).
+    pub fn observe_miner(&mut self, prev: &Miner, cur: &Miner) {
+        let won_sol = cur.lifetime_rewards_sol.saturating_sub(prev.lifetime_rewards_sol);
+        let won_ore = cur.lifetime_rewards_ore.saturating_sub(prev.lifetime_rewards_ore)
+            + cur.refined_ore.saturating_sub(prev.refined_ore);
+        let pnl = self.current();
+        pnl.won_sol += won_sol;
+        pnl.won_ore += won_ore;
+    }
+
+    pub fn current_hour(&self) -> Pnl {
+        *self.hours.last().unwrap()
+    }
+
+    pub fn total(&self) -> Pnl {
+        self.hours.iter().fold(Pnl::default(), |total, hour| Pnl {
+            spent: total.spent + hour.spent,
+            won_sol: total.won_sol + hour.won_sol,
+            won_ore: total.won_ore + hour.won_ore,
+        })
+    }
+
+    pub fn table(&self, ore_in_sol: f64) -> String {
+        let mut table = format!("{:>5} {:>12} {:>12} {:>12} {:>12}\n", "hour", "spent SOL", "won SOL", "won ORE", "net SOL");
+        let total = self.total();
+        let rows = self.hours.iter().enumerate().map(|(hour, pnl)| (hour.to_string(), pnl));
+        for (label, pnl) in rows.chain([("total".to_string(), &total)]) {
+            let _ = writeln!(
+                table,
+                "{:>5} {:>12.6} {:>12.6} {:>12.4} {:>12.6}",
+                label,
+                lamports_to_sol(pnl.spent),
+                lamports_to_sol(pnl.won_sol),
+                amount_to_ui_amount(pnl.won_ore, TOKEN_DECIMALS),
+                pnl.net_sol(ore_in_sol)
+            );
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steel::Zeroable;
+
+    #[test]
+    fn net_values_ore_at_the_given_price() {
+        let pnl = Pnl {
+            spent: 2_000_000_000,
+            won_sol: 500_000_000,
+            won_ore: 3 * 10u64.pow(TOKEN_DECIMALS as u32),
+        };
+        assert!((pnl.net_sol(0.25) - (-0.75)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn observe_miner_books_wins_and_refined_growth() {
+        let mut tracker = PnlTracker::new();
+        let prev = Miner::zeroed();
+        let mut cur = Miner::zeroed();
+        cur.lifetime_rewards_sol = 700;
+        cur.lifetime_rewards_ore = 40;
+        cur.refined_ore = 2;
+        tracker.record_spend(1_000);
+        tracker.record_spend(250);
+        tracker.observe_miner(&prev, &cur);
+        // a claim resets refined ORE, that is no negative win
+        let claimed = Miner { refined_ore: 0, ..cur };
+        tracker.observe_miner(&cur, &claimed);
+        let total = tracker.total();
+        assert_eq!((total.spent, total.won_sol, total.won_ore), (1_250, 700, 42));
+    }
+}
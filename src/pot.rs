@@ -0,0 +1,82 @@
+use tracing::info;
+
+const POT_EMA_ALPHA: f64 = 0.2;
+
+/// Exponential moving average of the final `total_deployed` of past rounds.
+#[derive(Debug, Default)]
+pub struct PotEma {
+    ema: Option<f64>,
+    pub rounds: u64,
+    warmup: u64,
+}
+
+impl PotEma {
+    pub fn new(warmup: u64) -> Self {
+        Self {
+            warmup,
+            ..Default::default()
+        }
+    }
+
+    pub fn observe(&mut self, total_deployed: u64) {
+        let pot = total_deployed as f64;
+        self.ema = Some(match self.ema {
+            Some(ema) => ema + POT_EMA_ALPHA * (pot - ema),
+            None => pot,
+        });
+        self.rounds += 1;
+        if self.rounds == self.warmup {
+            info!("pot ema warmed up after {} rounds: {:.4} SOL", self.rounds, pot / 1e9);
+        }
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        self.ema
+    }
+
+    /// Resumes from a saved average.
+    pub fn restore(&mut self, ema: Option<f64>, rounds: u64) {
+        self.ema = ema;
+        self.rounds = rounds;
+    }
+
+    /// The average once at least `warmup` rounds were observed, before that
+    /// it has not converged and callers should fall back to a static value.
+    pub fn trusted(&self) -> Option<f64> {
+        self.ema.filter(|_| self.rounds >= self.warmup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_observation_seeds_the_average() {
+        let mut pot = PotEma::new(2);
+        assert_eq!(pot.value(), None);
+        pot.observe(1_000);
+        assert_eq!(pot.value(), Some(1_000.0));
+        pot.observe(2_000);
+        assert_eq!(pot.value(), Some(1_200.0));
+    }
+
+    #[test]
+    fn trusted_only_after_warmup() {
+        let mut pot = PotEma::new(2);
+        pot.observe(1_000);
+        assert_eq!(pot.trusted(), None);
+        pot.observe(1_000);
+        assert_eq!(pot.trusted(), Some(1_000.0));
+    }
+
+    #[test]
+    fn restore_counts_towards_warmup() {
+        let mut pot = PotEma::new(3);
+        pot.restore(Some(5_000.0), 3);
+        assert_eq!(pot.trusted(), Some(5_000.0));
+        pot.observe(10_000);
+        assert_eq!(pot.rounds, 4);
+        assert_eq!(pot.value(), Some(6_000.0));
+    }
+}
@@ -1,26 +1,385 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use futures_util::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use solana_client::client_error::reqwest;
+use solana_client::client_error::reqwest::StatusCode;
+use tracing::{info, warn};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const ORE_MINT: &str = "oreoU2P8bN6jkk3jbaiVxYnG1dCXcYxwhwyK9jSybcp";
+const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+const COINGECKO_ORE_ID: &str = "ore";
+const COINGECKO_SOL_ID: &str = "solana";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PriceInfo {
     #[serde(rename = "usdPrice")]
-    pub usd_price: f64,
+    pub usd_price: Option<f64>,
     #[serde(rename = "blockId")]
-    pub block_id: i64,
-    pub decimals: i64,
+    pub block_id: Option<i64>,
+    pub decimals: Option<i64>,
     #[serde(rename = "priceChange24h")]
-    pub price_change24h: f64,
+    pub price_change24h: Option<f64>,
+}
+
+/// Prices a source returned, in USDC. A token that is missing from the
+/// response, or quoted as zero, negative or NaN, is `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceQuote {
+    pub ore: Option<f64>,
+    pub sol: Option<f64>,
+}
+
+impl PriceQuote {
+    fn parse(body: &str) -> anyhow::Result<Self> {
+        let prices: HashMap<String, Option<PriceInfo>> = serde_json::from_str(body)?;
+        let usable = |mint: &str| {
+            prices
+                .get(mint)
+                .and_then(|info| info.as_ref())
+                .and_then(|info| info.usd_price)
+                .filter(|price| price.is_finite() && *price > 0.0)
+        };
+        Ok(Self {
+            ore: usable(ORE_MINT),
+            sol: usable(SOL_MINT),
+        })
+    }
+
+    fn parse_coingecko(body: &str) -> anyhow::Result<Self> {
+        let prices: HashMap<String, Option<HashMap<String, Option<f64>>>> = serde_json::from_str(body)?;
+        let usable = |id: &str| {
+            prices
+                .get(id)
+                .and_then(|quote| quote.as_ref())
+                .and_then(|quote| quote.get("usd"))
+                .copied()
+                .flatten()
+                .filter(|price| price.is_finite() && *price > 0.0)
+        };
+        Ok(Self {
+            ore: usable(COINGECKO_ORE_ID),
+            sol: usable(COINGECKO_SOL_ID),
+        })
+    }
+
+    /// Both prices, as `(ore, sol)`, when both are available.
+    pub fn both(&self) -> Option<(f64, f64)> {
+        Some((self.ore?, self.sol?))
+    }
+}
+
+/// Returned by a price source when its api answers with 429.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "price api rate limited (retry after {:?})", self.retry_after)
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Somewhere to get the ORE and SOL price in USDC from.
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn get(&self) -> BoxFuture<'_, anyhow::Result<PriceQuote>>;
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceSourceKind {
+    Jupiter,
+    Coingecko,
 }
 
-pub async fn get_price() -> anyhow::Result<(f64, f64)> {
-    let url = "https://lite-api.jup.ag/price/v3?ids=So11111111111111111111111111111111111111112,oreoU2P8bN6jkk3jbaiVxYnG1dCXcYxwhwyK9jSybcp";
-    let resp = reqwest::get(url).await?.text().await?;
-    let prices: HashMap<String, PriceInfo> = serde_json::from_str(&resp)?;
-    let ore_price = prices.get("oreoU2P8bN6jkk3jbaiVxYnG1dCXcYxwhwyK9jSybcp");
-    let sol_price = prices.get("So11111111111111111111111111111111111111112");
-    if let (Some(ore), Some(sol)) = (ore_price, sol_price) {
-        return Ok((ore.usd_price, sol.usd_price));
+impl PriceSourceKind {
+    pub fn source(self) -> Box<dyn PriceSource> {
+        match self {
+            PriceSourceKind::Jupiter => Box::new(JupiterSource),
+            PriceSourceKind::Coingecko => Box::new(CoinGeckoSource),
+        }
+    }
+}
+
+pub struct JupiterSource;
+
+impl PriceSource for JupiterSource {
+    fn name(&self) -> &'static str {
+        "jupiter"
+    }
+
+    fn get(&self) -> BoxFuture<'_, anyhow::Result<PriceQuote>> {
+        Box::pin(async {
+            let url = format!("https://lite-api.jup.ag/price/v3?ids={},{}", SOL_MINT, ORE_MINT);
+            PriceQuote::parse(&fetch(&url).await?)
+        })
+    }
+}
+
+pub struct CoinGeckoSource;
+
+impl PriceSource for CoinGeckoSource {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn get(&self) -> BoxFuture<'_, anyhow::Result<PriceQuote>> {
+        Box::pin(async {
+            let url = format!(
+                "https://api.coingecko.com/api/v3/simple/price?ids={},{}&vs_currencies=usd",
+                COINGECKO_ORE_ID, COINGECKO_SOL_ID
+            );
+            PriceQuote::parse_coingecko(&fetch(&url).await?)
+        })
+    }
+}
+
+/// GETs `url`, turning a 429 into `RateLimited`.
+async fn fetch(url: &str) -> anyhow::Result<String> {
+    let resp = reqwest::get(url).await?;
+    if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = resp
+            .headers()
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return Err(RateLimited { retry_after }.into());
+    }
+    Ok(resp.error_for_status()?.text().await?)
+}
+
+/// Caches the last (ore, sol) price so the loop never hits the price sources
+/// more than once per `min_refresh`, and backs off when they answer 429.
+/// Sources are tried in order, the first complete quote wins.
+pub struct PriceCache {
+    sources: Vec<Box<dyn PriceSource>>,
+    source: Option<&'static str>,
+    last: Option<(f64, f64)>,
+    fetched_at: Option<Instant>,
+    min_refresh: Duration,
+    backoff: Duration,
+    backoff_until: Option<Instant>,
+}
+
+impl PriceCache {
+    pub fn new(min_refresh: Duration, sources: Vec<Box<dyn PriceSource>>) -> Self {
+        Self {
+            sources,
+            source: None,
+            last: None,
+            fetched_at: None,
+            min_refresh,
+            backoff: MIN_BACKOFF,
+            backoff_until: None,
+        }
+    }
+
+    /// The source the cached price came from.
+    pub fn source(&self) -> Option<&'static str> {
+        self.source
+    }
+
+    /// True while the last refresh attempt failed and the cached price is served.
+    pub fn is_stale(&self) -> bool {
+        self.backoff_until.is_some()
+    }
+
+    /// Returns the cached price while it is fresh (or while backing off),
+    /// otherwise refreshes it. Only errors if no price was ever fetched; the
+    /// backoff applies from the first failure, with or without a cached price.
+    pub async fn get_price_with_retry(&mut self) -> anyhow::Result<(f64, f64)> {
+        let now = Instant::now();
+        let fresh = self
+            .fetched_at
+            .is_some_and(|t| now.duration_since(t) < self.min_refresh);
+        if let Some(until) = self.backoff_until.filter(|t| now < *t) {
+            return self
+                .last
+                .ok_or_else(|| anyhow::anyhow!("no price yet, next try in {:?}", until - now));
+        }
+        if let (true, Some(last)) = (fresh, self.last) {
+            return Ok(last);
+        }
+
+        let price = self.fetch().await;
+        match price {
+            Ok((source, price)) => {
+                if self.source != Some(source) {
+                    info!("price source: {}", source);
+                }
+                self.source = Some(source);
+                self.last = Some(price);
+                self.fetched_at = Some(now);
+                self.backoff = MIN_BACKOFF;
+                self.backoff_until = None;
+                Ok(price)
+            }
+            Err(err) => {
+                let wait = match err.downcast_ref::<RateLimited>() {
+                    Some(limited) => {
+                        let wait = limited.retry_after.unwrap_or(self.backoff);
+                        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        wait
+                    }
+                    None => MIN_BACKOFF,
+                };
+                self.backoff_until = Some(now + wait);
+
+                match self.last {
+                    Some(last) => {
+                        warn!("get price failed: {:?}, using cached price, next try in {:?}", err, wait);
+                        Ok(last)
+                    }
+                    None => Err(err),
+                }
+            }
+        }
+    }
+
+    /// The first complete quote from the sources in order, with its source.
+    /// Fails with the last source's error when none of them has one.
+    async fn fetch(&self) -> anyhow::Result<(&'static str, (f64, f64))> {
+        let mut last_err = anyhow::anyhow!("no price source configured");
+        for source in &self.sources {
+            // the refined instruction needs both prices, a partial quote is a failed refresh
+            let price = source.get().await.and_then(|quote| {
+                quote
+                    .both()
+                    .ok_or_else(|| anyhow::anyhow!("incomplete price quote: {:?}", quote))
+            });
+            match price {
+                Ok(price) => return Ok((source.name(), price)),
+                Err(err) => {
+                    if self.sources.len() > 1 {
+                        warn!("price source {} failed: {:?}", source.name(), err);
+                    }
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jupiter(ore: &str, sol: &str) -> String {
+        format!(r#"{{"{}": {}, "{}": {}}}"#, ORE_MINT, ore, SOL_MINT, sol)
+    }
+
+    #[test]
+    fn parse_complete_jupiter_quote() {
+        let body = jupiter(
+            r#"{"usdPrice": 412.5, "blockId": 1, "decimals": 11, "priceChange24h": -2.5}"#,
+            r#"{"usdPrice": 187.25, "blockId": 1, "decimals": 9, "priceChange24h": 0.1}"#,
+        );
+        let quote = PriceQuote::parse(&body).unwrap();
+        assert_eq!(quote.both(), Some((412.5, 187.25)));
+    }
+
+    #[test]
+    fn parse_partial_jupiter_quotes() {
+        let quote = PriceQuote::parse(&format!(r#"{{"{}": {{"usdPrice": 187.25}}}}"#, SOL_MINT)).unwrap();
+        assert_eq!(quote, PriceQuote { ore: None, sol: Some(187.25) });
+        assert_eq!(quote.both(), None);
+
+        let quote = PriceQuote::parse(&jupiter("null", r#"{"usdPrice": 187.25}"#)).unwrap();
+        assert_eq!(quote.ore, None);
+
+        let quote = PriceQuote::parse(&jupiter(r#"{"usdPrice": null}"#, r#"{"usdPrice": 0.0}"#)).unwrap();
+        assert_eq!(quote, PriceQuote { ore: None, sol: None });
+
+        let quote = PriceQuote::parse(&jupiter(r#"{"usdPrice": -1.0}"#, r#"{}"#)).unwrap();
+        assert_eq!(quote, PriceQuote { ore: None, sol: None });
+    }
+
+    #[test]
+    fn parse_malformed_jupiter_bodies() {
+        assert!(PriceQuote::parse("").is_err());
+        assert!(PriceQuote::parse("<html>rate limited</html>").is_err());
+        assert!(PriceQuote::parse(&jupiter(r#"{"usdPrice": "412.5"}"#, "null")).is_err());
+        assert!(PriceQuote::parse(r#"{"data": [1, 2"#).is_err());
+    }
+
+    #[test]
+    fn parse_coingecko_quotes() {
+        let quote = PriceQuote::parse_coingecko(r#"{"ore": {"usd": 412.5}, "solana": {"usd": 187.25}}"#).unwrap();
+        assert_eq!(quote.both(), Some((412.5, 187.25)));
+
+        let quote = PriceQuote::parse_coingecko(r#"{"solana": {"usd": 187.25}, "ore": {}}"#).unwrap();
+        assert_eq!(quote, PriceQuote { ore: None, sol: Some(187.25) });
+
+        // null is no quote, the same as with jupiter
+        let quote = PriceQuote::parse_coingecko(r#"{"ore": {"usd": null}, "solana": null}"#).unwrap();
+        assert_eq!(quote, PriceQuote { ore: None, sol: None });
+
+        assert!(PriceQuote::parse_coingecko(r#"{"ore": {"usd": "412.5"}}"#).is_err());
+        assert!(PriceQuote::parse_coingecko("[]").is_err());
+    }
+
+    struct FixedSource(&'static str, Option<PriceQuote>);
+
+    impl PriceSource for FixedSource {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn get(&self) -> BoxFuture<'_, anyhow::Result<PriceQuote>> {
+            Box::pin(async move { self.1.ok_or_else(|| anyhow::anyhow!("{} is down", self.0)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_falls_through_to_the_first_complete_quote() {
+        let mut cache = PriceCache::new(
+            Duration::from_secs(60),
+            vec![
+                Box::new(FixedSource("down", None)),
+                Box::new(FixedSource("partial", Some(PriceQuote { ore: Some(400.0), sol: None }))),
+                Box::new(FixedSource("full", Some(PriceQuote { ore: Some(412.5), sol: Some(187.25) }))),
+            ],
+        );
+        assert_eq!(cache.get_price_with_retry().await.unwrap(), (412.5, 187.25));
+        assert_eq!(cache.source(), Some("full"));
+        assert!(!cache.is_stale());
+    }
+
+    #[tokio::test]
+    async fn cache_fails_without_any_price() {
+        let mut cache = PriceCache::new(Duration::from_secs(60), vec![Box::new(FixedSource("down", None))]);
+        assert!(cache.get_price_with_retry().await.is_err());
+        assert!(cache.is_stale());
+    }
+
+    struct CountingSource(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl PriceSource for CountingSource {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn get(&self) -> BoxFuture<'_, anyhow::Result<PriceQuote>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Box::pin(async { Err(anyhow::anyhow!("down")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_backs_off_before_the_first_price() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut cache = PriceCache::new(Duration::from_secs(60), vec![Box::new(CountingSource(calls.clone()))]);
+        assert!(cache.get_price_with_retry().await.is_err());
+        assert!(cache.get_price_with_retry().await.is_err());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
     }
-    anyhow::bail!("Failed to get prices");
 }
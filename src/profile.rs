@@ -0,0 +1,56 @@
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty,
+    /// One JSON object per event, with the event fields as keys.
+    Json,
+}
+
+/// Keeps the chrome trace writer alive, the trace is flushed when it drops.
+#[cfg(feature = "profile")]
+pub type ProfileGuard = Option<tracing_chrome::FlushGuard>;
+#[cfg(not(feature = "profile"))]
+pub type ProfileGuard = ();
+
+/// Installs the log output and, with `--profile`, a chrome trace of the
+/// deploy path spans that chrome://tracing or Perfetto can open.
+pub fn init_tracing(format: LogFormat, profile: Option<&str>) -> anyhow::Result<ProfileGuard> {
+    let fmt = match format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(fmt);
+
+    #[cfg(feature = "profile")]
+    {
+        match profile {
+            Some(path) => {
+                let (chrome, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                    .file(path)
+                    .include_args(true)
+                    .build();
+                registry.with(chrome).init();
+                Ok(Some(guard))
+            }
+            None => {
+                registry.init();
+                Ok(None)
+            }
+        }
+    }
+
+    #[cfg(not(feature = "profile"))]
+    {
+        if profile.is_some() {
+            anyhow::bail!("--profile needs a build with `--features profile`");
+        }
+        registry.init();
+        Ok(())
+    }
+}
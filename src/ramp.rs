@@ -0,0 +1,90 @@
+use std::path::Path;
+use ore_api::prelude::Round;
+use tracing::info;
+
+use crate::shadow::ShadowLedger;
+use crate::stats::StatsEvent;
+
+/// Observe-only start: the first `observe_rounds` rounds are paper traded at
+/// the live `ore_refined_rate`, then the bot goes live if the paper win rate
+/// reached `min_win_rate` (and, if configured, once `confirm_file` exists).
+pub struct Ramp {
+    ledger: ShadowLedger,
+    observe_rounds: u64,
+    min_win_rate: f64,
+    confirm_file: Option<String>,
+    live: bool,
+}
+
+impl Ramp {
+    pub fn new(ore_refined_rate: f64, observe_rounds: u64, min_win_rate: f64, confirm_file: Option<String>) -> Self {
+        if observe_rounds > 0 {
+            info!("observe-only for the first {} rounds, going live at a paper win rate >= {}", observe_rounds, min_win_rate);
+        }
+        Self {
+            ledger: ShadowLedger::new(ore_refined_rate),
+            observe_rounds,
+            min_win_rate,
+            confirm_file,
+            live: observe_rounds == 0,
+        }
+    }
+
+    pub fn is_live(&mut self) -> bool {
+        if !self.live && self.ledger.rounds() >= self.observe_rounds {
+            self.check();
+        }
+        self.live
+    }
+
+    pub fn has_position(&self, round_id: u64) -> bool {
+        self.ledger.has_position(round_id)
+    }
+
+    pub fn observe(&mut self, round_id: u64, deployed: [u64; 25]) {
+        self.ledger.open(round_id, deployed);
+    }
+
+    pub fn settle(&mut self, round: &Round) -> Option<StatsEvent> {
+        if self.live {
+            return None;
+        }
+        self.ledger.settle(round)
+    }
+
+    fn check(&mut self) {
+        let win_rate = self.ledger.win_rate().unwrap_or(0.0);
+        if win_rate < self.min_win_rate {
+            return;
+        }
+        if let Some(path) = &self.confirm_file {
+            if !Path::new(path).exists() {
+                info!("paper win rate {:.2} after {} rounds meets the criterion, create {} to go live", win_rate, self.ledger.rounds(), path);
+                return;
+            }
+        }
+        info!("paper win rate {:.2} after {} rounds, going live", win_rate, self.ledger.rounds());
+        self.live = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_observe_rounds_starts_live() {
+        let mut ramp = Ramp::new(0.5, 0, 0.5, None);
+        assert!(ramp.is_live());
+    }
+
+    #[test]
+    fn observe_only_until_rounds_settled() {
+        let mut ramp = Ramp::new(0.5, 3, 0.0, None);
+        assert!(!ramp.is_live());
+        ramp.observe(7, [0; 25]);
+        assert!(ramp.has_position(7));
+        assert!(!ramp.has_position(8));
+        assert!(!ramp.is_live());
+    }
+}
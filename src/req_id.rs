@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+/// A transaction can't land once its blockhash is older than this.
+const TX_LIVE_SLOTS: u64 = 150;
+
+/// Hands out the refined instruction's `req_id`, wrapping at `space`, and
+/// never reuses one while a transaction carrying it could still land.
+pub struct ReqIds {
+    space: u16,
+    next: u16,
+    used_at: HashMap<u8, u64>,
+}
+
+impl ReqIds {
+    /// `space` is capped at 256, `req_id` is a `u8` on-chain.
+    pub fn new(space: u16) -> Self {
+        Self {
+            space: space.clamp(1, 256),
+            next: 0,
+            used_at: HashMap::new(),
+        }
+    }
+
+    /// The next `req_id` that is free at `slot`, `None` if all of them are
+    /// still carried by live transactions.
+    pub fn next(&mut self, slot: u64) -> Option<u8> {
+        for _ in 0..self.space {
+            let req_id = self.next as u8;
+            self.next = (self.next + 1) % self.space;
+            let live = self
+                .used_at
+                .get(&req_id)
+                .is_some_and(|used| slot <= used + TX_LIVE_SLOTS);
+            if !live {
+                return Some(req_id);
+            }
+        }
+        None
+    }
+
+    pub fn mark_used(&mut self, req_id: u8, slot: u64) {
+        self.used_at.insert(req_id, slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around_the_space() {
+        let mut req_ids = ReqIds::new(3);
+        let ids = (0..7).map(|_| req_ids.next(1_000).unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids, [0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn full_space_wraps_past_255() {
+        let mut req_ids = ReqIds::new(1_000);
+        for expected in 0..=255u8 {
+            assert_eq!(req_ids.next(0), Some(expected));
+        }
+        assert_eq!(req_ids.next(0), Some(0));
+    }
+
+    #[test]
+    fn skips_ids_still_carried_by_live_transactions() {
+        let mut req_ids = ReqIds::new(3);
+        let first = req_ids.next(1_000).unwrap();
+        req_ids.mark_used(first, 1_000);
+        let second = req_ids.next(1_000).unwrap();
+        req_ids.mark_used(second, 1_000);
+        // wrapped around, the only free id is the one never marked
+        assert_eq!(req_ids.next(1_100), Some(2));
+        assert_eq!(req_ids.next(1_100), Some(2));
+        // ... until every id is live
+        req_ids.mark_used(2, 1_100);
+        assert_eq!(req_ids.next(1_100), None);
+        // and free again once the blockhash the first one used expired
+        assert_eq!(req_ids.next(1_000 + TX_LIVE_SLOTS + 1), Some(first));
+    }
+}
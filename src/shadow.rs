@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use ore_api::prelude::Round;
+use tracing::info;
+
+use crate::stats::StatsEvent;
+
+/// Paper ledger of a challenger `ore_refined_rate` evaluated next to the live
+/// one. Positions come from simulating the refined instruction, results from
+/// the winning square once the round has been reset.
+pub struct ShadowLedger {
+    pub ore_refined_rate: f64,
+    positions: HashMap<u64, [u64; 25]>,
+    rounds: u64,
+    wins: u64,
+    deployed: u64,
+}
+
+impl ShadowLedger {
+    pub fn new(ore_refined_rate: f64) -> Self {
+        Self {
+            ore_refined_rate,
+            positions: HashMap::new(),
+            rounds: 0,
+            wins: 0,
+            deployed: 0,
+        }
+    }
+
+    pub fn has_position(&self, round_id: u64) -> bool {
+        self.positions.contains_key(&round_id)
+    }
+
+    pub fn open(&mut self, round_id: u64, deployed: [u64; 25]) {
+        info!("shadow rate {}: would deploy {:?} in round {}", self.ore_refined_rate, deployed, round_id);
+        self.positions.insert(round_id, deployed);
+    }
+
+    /// Number of settled paper rounds.
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    pub fn win_rate(&self) -> Option<f64> {
+        (self.rounds > 0).then(|| self.wins as f64 / self.rounds as f64)
+    }
+
+    /// Settles the shadow position for a finished round, if any.
+    pub fn settle(&mut self, round: &Round) -> Option<StatsEvent> {
+        let deployed = self.positions.remove(&round.id)?;
+        let winning_square = round.winning_square(round.rng()?);
+        let amount = deployed.iter().sum::<u64>();
+        let won = deployed[winning_square] > 0;
+
+        self.rounds += 1;
+        self.deployed += amount;
+        if won {
+            self.wins += 1;
+        }
+        info!(
+            "shadow rate {}: round {} winning square {} won: {} ({} wins / {} rounds, {} lamports deployed)",
+            self.ore_refined_rate, round.id, winning_square, won, self.wins, self.rounds, self.deployed
+        );
+
+        Some(StatsEvent::Shadow {
+            round_id: round.id,
+            ore_refined_rate: self.ore_refined_rate,
+            amount,
+            won,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steel::Zeroable;
+
+    #[test]
+    fn positions_are_tracked_per_round() {
+        let mut ledger = ShadowLedger::new(0.5);
+        assert_eq!(ledger.win_rate(), None);
+        ledger.open(3, [1; 25]);
+        assert!(ledger.has_position(3));
+        assert!(!ledger.has_position(4));
+        assert_eq!(ledger.rounds(), 0);
+    }
+
+    #[test]
+    fn settle_without_position_is_a_no_op() {
+        let mut ledger = ShadowLedger::new(0.5);
+        let mut round = Round::zeroed();
+        round.id = 9;
+        assert!(ledger.settle(&round).is_none());
+        assert_eq!(ledger.rounds(), 0);
+    }
+}
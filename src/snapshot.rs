@@ -0,0 +1,124 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use ore_api::prelude::*;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use steel::Clock;
+use tracing::info;
+
+use crate::error::deserialize_account;
+use crate::utils::get_board;
+
+/// Self-contained capture of one round: the raw account data of board, round,
+/// treasury, config and clock, all read in a single `getMultipleAccounts` call
+/// so they share one slot. Decoding goes through the same `deserialize_account`
+/// the live loop uses, so a loaded snapshot reproduces the situation exactly.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoundSnapshot {
+    pub round_id: u64,
+    pub slot: u64,
+    pub captured_at: u64,
+    pub board: Vec<u8>,
+    pub round: Vec<u8>,
+    pub treasury: Vec<u8>,
+    pub config: Vec<u8>,
+    pub clock: Vec<u8>,
+}
+
+impl RoundSnapshot {
+    pub async fn capture(rpc: &RpcClient, round_id: Option<u64>) -> anyhow::Result<Self> {
+        let round_id = match round_id {
+            Some(round_id) => round_id,
+            None => get_board(rpc).await?.round_id,
+        };
+        let addresses = [
+            ore_api::state::board_pda().0,
+            ore_api::state::round_pda(round_id).0,
+            ore_api::state::treasury_pda().0,
+            ore_api::state::config_pda().0,
+            solana_sdk::sysvar::clock::ID,
+        ];
+        let response = rpc
+            .get_multiple_accounts_with_commitment(&addresses, rpc.commitment())
+            .await?;
+        let mut data = vec![];
+        for (address, account) in addresses.iter().zip(response.value) {
+            let Some(account) = account else {
+                anyhow::bail!("account {} not found (round {})", address, round_id);
+            };
+            data.push(account.data);
+        }
+        let [board, round, treasury, config, clock]: [Vec<u8>; 5] = data.try_into().unwrap();
+
+        Ok(Self {
+            round_id,
+            slot: response.context.slot,
+            captured_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            board,
+            round,
+            treasury,
+            config,
+            clock,
+        })
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn board(&self) -> anyhow::Result<Board> {
+        Ok(deserialize_account::<Board>(&ore_api::state::board_pda().0, &self.board)?)
+    }
+
+    pub fn round(&self) -> anyhow::Result<Round> {
+        Ok(deserialize_account::<Round>(&ore_api::state::round_pda(self.round_id).0, &self.round)?)
+    }
+
+    pub fn clock(&self) -> anyhow::Result<Clock> {
+        Ok(bincode::deserialize::<Clock>(&self.clock)?)
+    }
+}
+
+pub async fn dump_round(rpc: &RpcClient, round_id: Option<u64>, out: Option<String>) -> anyhow::Result<()> {
+    let snapshot = RoundSnapshot::capture(rpc, round_id).await?;
+    let round = snapshot.round()?;
+    let clock = snapshot.clock()?;
+    let path = out.unwrap_or_else(|| format!("round-{}.json", snapshot.round_id));
+    snapshot.save(&path)?;
+    info!(
+        "round {} at slot {} (unix {}): {} lamports deployed, written to {}",
+        snapshot.round_id, snapshot.slot, clock.unix_timestamp, round.total_deployed, path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let snapshot = RoundSnapshot {
+            round_id: 42,
+            slot: 1_000,
+            captured_at: 1_700_000_000,
+            board: vec![1, 2, 3],
+            round: vec![4, 5],
+            treasury: vec![6],
+            config: vec![],
+            clock: vec![7, 8, 9],
+        };
+        let path = std::env::temp_dir().join(format!("ore-snapshot-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        snapshot.save(path).unwrap();
+        let loaded = RoundSnapshot::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!((loaded.round_id, loaded.slot, loaded.captured_at), (42, 1_000, 1_700_000_000));
+        assert_eq!((loaded.board, loaded.round, loaded.clock), (vec![1, 2, 3], vec![4, 5], vec![7, 8, 9]));
+    }
+}
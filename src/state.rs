@@ -0,0 +1,142 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::balance::DAY;
+
+const DEPLOYED_ROUNDS_KEPT: u64 = 100;
+
+/// What a restart should not forget: learned averages, the current tip,
+/// cooldowns and which rounds were already played. Written after every round
+/// and every deploy, ignored on startup once older than the configured max age
+/// except for the daily spends, which expire by their own timestamps.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionState {
+    pub saved_at: u64,
+    pub pot_ema: Option<f64>,
+    pub pot_rounds: u64,
+    pub landing_latency_slots: Option<f64>,
+    pub jito_tip: Option<u64>,
+    pub loss_cooldown_until: u64,
+    pub last_round_id: u64,
+    /// Recent rounds a deploy was submitted in.
+    pub deployed_rounds: BTreeSet<u64>,
+    /// Lamports deployed plus fees since the state file was created.
+    pub spent: u64,
+    pub earned_sol: u64,
+    pub earned_ore: u64,
+    /// Landed spend of the last 24 hours, for `--max-daily-spend-sol`.
+    pub daily_spends: Vec<(SystemTime, u64)>,
+}
+
+impl SessionState {
+    pub fn load(path: &str, max_age: Duration) -> anyhow::Result<Option<Self>> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut state: SessionState = serde_json::from_slice(&data)?;
+        let now_time = SystemTime::now();
+        state.daily_spends.retain(|(at, _)| now_time.duration_since(*at).unwrap_or_default() < DAY);
+        let age = Duration::from_secs(now()?.saturating_sub(state.saved_at));
+        if age > max_age {
+            // the daily cap spans longer than the usual max age, a restart
+            // must not reset it
+            info!("session state in {} is {:?} old, starting fresh but keeping the last 24h of spend", path, age);
+            return Ok(Some(Self {
+                daily_spends: state.daily_spends,
+                ..Default::default()
+            }));
+        }
+        info!("restored session state from {} ({:?} old)", path, age);
+        Ok(Some(state))
+    }
+
+    pub fn record_deploy(&mut self, round_id: u64) {
+        self.deployed_rounds.insert(round_id);
+        self.deployed_rounds = self.deployed_rounds.split_off(&round_id.saturating_sub(DEPLOYED_ROUNDS_KEPT));
+    }
+
+    pub fn save(&mut self, path: &str) -> anyhow::Result<()> {
+        self.saved_at = now()?;
+        // write then rename so a crash mid-write never leaves a torn file
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+fn now() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_deploy_keeps_recent_rounds() {
+        let mut state = SessionState::default();
+        state.record_deploy(1);
+        state.record_deploy(50);
+        assert_eq!(state.deployed_rounds.iter().copied().collect::<Vec<_>>(), vec![1, 50]);
+        state.record_deploy(150);
+        assert_eq!(state.deployed_rounds.iter().copied().collect::<Vec<_>>(), vec![50, 150]);
+    }
+
+    #[test]
+    fn save_load_roundtrip() {
+        let path = std::env::temp_dir().join(format!("ore-state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut state = SessionState {
+            pot_ema: Some(1.5e9),
+            jito_tip: Some(10_000),
+            spent: 42,
+            daily_spends: vec![(SystemTime::now(), 7)],
+            ..Default::default()
+        };
+        state.record_deploy(9);
+        state.save(path).unwrap();
+
+        let loaded = SessionState::load(path, Duration::from_secs(60)).unwrap().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.pot_ema, Some(1.5e9));
+        assert_eq!(loaded.jito_tip, Some(10_000));
+        assert_eq!(loaded.spent, 42);
+        assert!(loaded.deployed_rounds.contains(&9));
+        assert_eq!(loaded.daily_spends, state.daily_spends);
+    }
+
+    #[test]
+    fn missing_state_is_ignored() {
+        let path = std::env::temp_dir().join(format!("ore-state-missing-{}.json", std::process::id()));
+        assert!(SessionState::load(path.to_str().unwrap(), Duration::from_secs(60)).unwrap().is_none());
+    }
+
+    #[test]
+    fn stale_state_keeps_only_recent_daily_spends() {
+        let path = std::env::temp_dir().join(format!("ore-state-stale-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let recent = (SystemTime::now() - Duration::from_secs(2 * 60 * 60), 7);
+        let expired = (SystemTime::now() - DAY - Duration::from_secs(60), 9);
+        let state = SessionState {
+            saved_at: now().unwrap() - 2 * 60 * 60,
+            pot_ema: Some(1.5e9),
+            spent: 5,
+            daily_spends: vec![expired, recent],
+            ..Default::default()
+        };
+        std::fs::write(path, serde_json::to_vec(&state).unwrap()).unwrap();
+
+        let loaded = SessionState::load(path, Duration::from_secs(60 * 60)).unwrap().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.saved_at, 0);
+        assert_eq!(loaded.pot_ema, None);
+        assert_eq!(loaded.spent, 0);
+        assert_eq!(loaded.daily_spends, vec![recent]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use futures_util::future::BoxFuture;
+use ore_api::prelude::{Miner, TOKEN_DECIMALS};
+use serde::Serialize;
+use solana_sdk::native_token::lamports_to_sol;
+use spl_token::amount_to_ui_amount;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StatsEvent {
+    Deploy {
+        round_id: u64,
+        amount: u64,
+        channel: &'static str,
+        signature: String,
+        bundle_id: Option<String>,
+    },
+    Claim {
+        round_id: u64,
+        sol: u64,
+        ore: u64,
+    },
+    Win {
+        round_id: u64,
+        sol: u64,
+        ore: u64,
+    },
+    Fee {
+        round_id: u64,
+        program_fee: u64,
+        priority_fee: u64,
+        jito_tip: u64,
+    },
+    Shadow {
+        round_id: u64,
+        ore_refined_rate: f64,
+        amount: u64,
+        won: bool,
+    },
+}
+
+/// Destination for session accounting. Implementations must never make the
+/// mining loop fail, callers only log the returned error.
+pub trait StatsSink: Send + Sync {
+    fn record(&self, event: StatsEvent) -> BoxFuture<'_, anyhow::Result<()>>;
+    fn flush(&self) -> BoxFuture<'_, anyhow::Result<()>>;
+}
+
+pub struct NoopStatsSink;
+
+impl StatsSink for NoopStatsSink {
+    fn record(&self, _event: StatsEvent) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Keeps every event in memory, for tests that check what the loop recorded.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStatsSink {
+    pub events: std::sync::Mutex<Vec<StatsEvent>>,
+}
+
+#[cfg(test)]
+impl StatsSink for MemoryStatsSink {
+    fn record(&self, event: StatsEvent) -> BoxFuture<'_, anyhow::Result<()>> {
+        self.events.lock().unwrap().push(event);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[derive(Serialize)]
+struct JsonlRecord<'a> {
+    ts: u64,
+    #[serde(flatten)]
+    event: &'a StatsEvent,
+}
+
+pub struct JsonlStatsSink {
+    file: Mutex<File>,
+}
+
+impl JsonlStatsSink {
+    pub async fn open(path: &str) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path).await?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl StatsSink for JsonlStatsSink {
+    fn record(&self, event: StatsEvent) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let mut line = serde_json::to_string(&JsonlRecord { ts, event: &event })?;
+            line.push('\n');
+            self.file.lock().await.write_all(line.as_bytes()).await?;
+            Ok(())
+        })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.file.lock().await.flush().await?;
+            Ok(())
+        })
+    }
+}
+
+/// What one run of the bot did, printed on shutdown.
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub rounds: u64,
+    pub spent: u64,
+    pub earned_sol: u64,
+    pub earned_ore: u64,
+    last_round: Option<u64>,
+}
+
+impl RunSummary {
+    pub fn record_deploy(&mut self, round_id: u64, spent: u64) {
+        if self.last_round != Some(round_id) {
+            self.rounds += 1;
+            self.last_round = Some(round_id);
+        }
+        self.spent += spent;
+    }
+
+    pub fn record_win(&mut self, sol: u64, ore: u64) {
+        self.earned_sol += sol;
+        self.earned_ore += ore;
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rounds played, {} SOL spent, {} SOL and {} ORE won",
+            self.rounds,
+            lamports_to_sol(self.spent),
+            lamports_to_sol(self.earned_sol),
+            amount_to_ui_amount(self.earned_ore, TOKEN_DECIMALS)
+        )
+    }
+}
+
+/// Derives win/claim events from two consecutive miner snapshots. Wins are
+/// taken from the lifetime counters since a checkpoint and a claim landing in
+/// the same transaction cancel out in `rewards_sol`.
+pub fn miner_events(prev: &Miner, cur: &Miner) -> Vec<StatsEvent> {
+    let mut events = vec![];
+    let win_sol = cur.lifetime_rewards_sol.saturating_sub(prev.lifetime_rewards_sol);
+    let win_ore = cur.lifetime_rewards_ore.saturating_sub(prev.lifetime_rewards_ore);
+    if win_sol > 0 || win_ore > 0 {
+        events.push(StatsEvent::Win {
+            round_id: cur.round_id,
+            sol: win_sol,
+            ore: win_ore,
+        });
+    }
+
+    let claim_sol = (prev.rewards_sol + win_sol).saturating_sub(cur.rewards_sol);
+    let claim_ore = (prev.rewards_ore + win_ore).saturating_sub(cur.rewards_ore);
+    if claim_sol > 0 || claim_ore > 0 {
+        events.push(StatsEvent::Claim {
+            round_id: cur.round_id,
+            sol: claim_sol,
+            ore: claim_ore,
+        });
+    }
+    events
+}
+
+pub async fn record(sink: &dyn StatsSink, event: StatsEvent) {
+    if let Err(err) = sink.record(event).await {
+        warn!("failed to record stats: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steel::Zeroable;
+
+    #[tokio::test]
+    async fn memory_sink_keeps_events_in_order() {
+        let sink = MemoryStatsSink::default();
+        record(&sink, StatsEvent::Win { round_id: 1, sol: 10, ore: 0 }).await;
+        record(&sink, StatsEvent::Fee { round_id: 2, program_fee: 5, priority_fee: 1, jito_tip: 0 }).await;
+        let events = sink.events.lock().unwrap();
+        assert!(matches!(events[..], [StatsEvent::Win { round_id: 1, .. }, StatsEvent::Fee { round_id: 2, program_fee: 5, .. }]));
+    }
+
+    #[test]
+    fn jsonl_record_flattens_the_tagged_event() {
+        let event = StatsEvent::Claim { round_id: 3, sol: 7, ore: 8 };
+        let line = serde_json::to_string(&JsonlRecord { ts: 100, event: &event }).unwrap();
+        assert_eq!(line, r#"{"ts":100,"event":"claim","round_id":3,"sol":7,"ore":8}"#);
+    }
+
+    #[test]
+    fn miner_events_split_wins_and_claims() {
+        let mut prev = Miner::zeroed();
+        prev.rewards_sol = 500;
+        let mut cur = prev;
+        cur.round_id = 9;
+        cur.lifetime_rewards_sol = 200;
+        // won 200 and claimed everything in the same transaction
+        cur.rewards_sol = 0;
+        let events = miner_events(&prev, &cur);
+        assert!(matches!(
+            events[..],
+            [StatsEvent::Win { round_id: 9, sol: 200, ore: 0 }, StatsEvent::Claim { round_id: 9, sol: 700, ore: 0 }]
+        ));
+        assert!(miner_events(&cur, &cur).is_empty());
+    }
+
+    #[test]
+    fn run_summary_counts_each_round_once() {
+        let mut summary = RunSummary::default();
+        summary.record_deploy(5, 100);
+        summary.record_deploy(5, 50);
+        summary.record_deploy(6, 25);
+        summary.record_win(10, 1);
+        assert_eq!((summary.rounds, summary.spent, summary.earned_sol, summary.earned_ore), (2, 175, 10, 1));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use tracing::info;
+
+const LANDING_WINDOW: usize = 20;
+const ESCALATE: f64 = 1.5;
+const EASE: f64 = 0.9;
+
+/// Adjusts the jito tip from observed bundle landings: escalate after a miss,
+/// ease back after a landing, always within `[min, max]`.
+pub struct TipController {
+    tip: u64,
+    min: u64,
+    max: u64,
+    adaptive: bool,
+    landings: VecDeque<bool>,
+}
+
+impl TipController {
+    pub fn new(min: u64, max: u64, adaptive: bool) -> Self {
+        Self {
+            tip: min,
+            min,
+            max: max.max(min),
+            adaptive,
+            landings: VecDeque::with_capacity(LANDING_WINDOW),
+        }
+    }
+
+    pub fn tip(&self) -> u64 {
+        self.tip
+    }
+
+    /// Resumes from a saved tip, kept within the configured bounds.
+    pub fn restore(&mut self, tip: u64) {
+        if self.adaptive {
+            self.tip = tip.clamp(self.min, self.max);
+        }
+    }
+
+    pub fn landing_rate(&self) -> Option<f64> {
+        if self.landings.is_empty() {
+            return None;
+        }
+        Some(self.landings.iter().filter(|l| **l).count() as f64 / self.landings.len() as f64)
+    }
+
+    pub fn observe(&mut self, landed: bool) {
+        if self.landings.len() == LANDING_WINDOW {
+            self.landings.pop_front();
+        }
+        self.landings.push_back(landed);
+        if !self.adaptive {
+            return;
+        }
+
+        let factor = if landed { EASE } else { ESCALATE };
+        self.tip = ((self.tip as f64 * factor) as u64).clamp(self.min, self.max);
+        info!(
+            "jito tip: {} lamports (landing rate {:.0}% over the last {} bundles)",
+            self.tip,
+            self.landing_rate().unwrap_or(0.0) * 100.0,
+            self.landings.len()
+        );
+    }
+}
+
+/// Compute unit price of the rpc submissions within a round: `base` for the
+/// first one, multiplied by `multiplier` on every resubmission, up to `max`.
+pub struct FeeEscalation {
+    base: u64,
+    multiplier: f64,
+    max: u64,
+    round_id: u64,
+    attempts: u32,
+}
+
+impl FeeEscalation {
+    pub fn new(base: u64, multiplier: f64, max: u64) -> Self {
+        Self {
+            base,
+            multiplier: multiplier.max(1.0),
+            max: max.max(base),
+            round_id: 0,
+            attempts: 0,
+        }
+    }
+
+    /// The attempt number within `round_id` and the unit price for it.
+    pub fn next(&mut self, round_id: u64) -> (u32, u64) {
+        if self.round_id != round_id {
+            self.round_id = round_id;
+            self.attempts = 0;
+        }
+        let price = (self.base as f64 * self.multiplier.powi(self.attempts as i32)).min(self.max as f64) as u64;
+        self.attempts += 1;
+        (self.attempts, price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tip_escalates_on_miss_and_eases_on_landing() {
+        let mut tips = TipController::new(1_000, 10_000, true);
+        tips.observe(false);
+        assert_eq!(tips.tip(), 1_500);
+        tips.observe(false);
+        assert_eq!(tips.tip(), 2_250);
+        tips.observe(true);
+        assert_eq!(tips.tip(), 2_025);
+    }
+
+    #[test]
+    fn tip_stays_within_bounds() {
+        let mut tips = TipController::new(1_000, 2_000, true);
+        tips.observe(true);
+        assert_eq!(tips.tip(), 1_000);
+        for _ in 0..5 {
+            tips.observe(false);
+        }
+        assert_eq!(tips.tip(), 2_000);
+        tips.restore(50_000);
+        assert_eq!(tips.tip(), 2_000);
+        tips.restore(10);
+        assert_eq!(tips.tip(), 1_000);
+    }
+
+    #[test]
+    fn fixed_tip_ignores_landings_and_restore() {
+        let mut tips = TipController::new(1_000, 10_000, false);
+        tips.observe(false);
+        tips.restore(5_000);
+        assert_eq!(tips.tip(), 1_000);
+        assert_eq!(tips.landing_rate(), Some(0.0));
+    }
+
+    #[test]
+    fn landing_rate_covers_the_last_window() {
+        let mut tips = TipController::new(1_000, 10_000, false);
+        assert_eq!(tips.landing_rate(), None);
+        for _ in 0..LANDING_WINDOW {
+            tips.observe(false);
+        }
+        for _ in 0..LANDING_WINDOW / 2 {
+            tips.observe(true);
+        }
+        assert_eq!(tips.landing_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn fee_escalates_within_a_round_and_resets_on_the_next() {
+        let mut fees = FeeEscalation::new(100, 2.0, 500);
+        assert_eq!(fees.next(1), (1, 100));
+        assert_eq!(fees.next(1), (2, 200));
+        assert_eq!(fees.next(1), (3, 400));
+        assert_eq!(fees.next(1), (4, 500));
+        assert_eq!(fees.next(2), (1, 100));
+    }
+
+    #[test]
+    fn fee_bounds_are_sanitized() {
+        let mut fees = FeeEscalation::new(100, 0.5, 10);
+        assert_eq!(fees.next(1), (1, 100));
+        assert_eq!(fees.next(1), (2, 100));
+    }
+}
@@ -1,17 +1,22 @@
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use anchor_lang::pubkey;
-use log::info;
+use log::{info, warn};
 use ore_api::prelude::*;
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::{
     client_error::{reqwest::StatusCode, ClientErrorKind},
     nonblocking::rpc_client::RpcClient,
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_config::{
+        RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionAccountsConfig,
+        RpcSimulateTransactionConfig,
+    },
     rpc_filter::{Memcmp, RpcFilterType},
 };
 use solana_client::rpc_response::{RpcResult, RpcSimulateTransactionResult};
 use solana_program::address_lookup_table::AddressLookupTableAccount;
+use solana_program::address_lookup_table::state::AddressLookupTable;
 use solana_program::instruction::Instruction;
 use solana_program::slot_hashes::SlotHashes;
 use solana_sdk::{
@@ -21,94 +26,485 @@ use solana_sdk::{
     signature::{read_keypair_file, Signer},
     transaction::Transaction,
 };
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::hash::Hash;
 use solana_sdk::message::{v0, VersionedMessage};
 use solana_sdk::native_token::lamports_to_sol;
 use solana_sdk::signature::{Keypair, Signature};
-use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
 use spl_associated_token_account::get_associated_token_address;
 use spl_token::amount_to_ui_amount;
 use steel::{AccountDeserialize, Clock, Discriminator};
+use tokio::task::JoinHandle;
 use crate::{jito, DEFALUT_UNITS};
-use crate::jito::send_bundle;
+use crate::error::{deserialize_account, OreError};
+use crate::jito::{send_bundle, BundleOutcome, JitoRegionSelector};
+
+pub const PRIORITY_FEE: u64 = 20000;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmountRounding {
+    Floor,
+    Round,
+    Ceil,
+}
+
+pub fn sol_to_lamports_rounded(sol: f64, rounding: AmountRounding) -> u64 {
+    let lamports = sol * 1e9f64;
+    let lamports = match rounding {
+        AmountRounding::Floor => lamports.floor(),
+        AmountRounding::Round => lamports.round(),
+        AmountRounding::Ceil => lamports.ceil(),
+    };
+    lamports as u64
+}
 
 pub async fn get_board(rpc: &RpcClient) -> Result<Board, anyhow::Error> {
     let board_pda = ore_api::state::board_pda();
     let account = rpc.get_account(&board_pda.0).await?;
-    let board = Board::try_from_bytes(&account.data)?;
-    Ok(*board)
+    let board = deserialize_account::<Board>(&board_pda.0, &account.data)?;
+    Ok(board)
 }
 
 
 pub async fn get_round(rpc: &RpcClient, id: u64) -> Result<Round, anyhow::Error> {
     let round_pda = ore_api::state::round_pda(id);
     let account = rpc.get_account(&round_pda.0).await?;
-    let round = Round::try_from_bytes(&account.data)?;
-    Ok(*round)
+    let round = deserialize_account::<Round>(&round_pda.0, &account.data)?;
+    Ok(round)
+}
+
+/// `None` while the round account doesn't exist yet, the board can move to a
+/// new round a slot or two before its round PDA is created.
+pub async fn get_round_opt(rpc: &RpcClient, id: u64) -> Result<Option<Round>, anyhow::Error> {
+    let round_pda = ore_api::state::round_pda(id);
+    let account = rpc
+        .get_account_with_commitment(&round_pda.0, rpc.commitment())
+        .await?
+        .value;
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    Ok(Some(deserialize_account::<Round>(&round_pda.0, &account.data)?))
+}
+
+pub async fn get_lookup_table(rpc: &RpcClient, address: Pubkey) -> Result<AddressLookupTableAccount, anyhow::Error> {
+    let account = rpc.get_account(&address).await?;
+    let table = AddressLookupTable::deserialize(&account.data)?;
+    Ok(AddressLookupTableAccount {
+        key: address,
+        addresses: table.addresses.to_vec(),
+    })
 }
 
 pub async fn get_treasury(rpc: &RpcClient) -> Result<Treasury, anyhow::Error> {
     let treasury_pda = ore_api::state::treasury_pda();
     let account = rpc.get_account(&treasury_pda.0).await?;
-    let treasury = Treasury::try_from_bytes(&account.data)?;
-    Ok(*treasury)
+    let treasury = deserialize_account::<Treasury>(&treasury_pda.0, &account.data)?;
+    Ok(treasury)
 }
 
 pub async fn get_config(rpc: &RpcClient) -> Result<Config, anyhow::Error> {
     let config_pda = ore_api::state::config_pda();
     let account = rpc.get_account(&config_pda.0).await?;
-    let config = Config::try_from_bytes(&account.data)?;
-    Ok(*config)
+    let config = deserialize_account::<Config>(&config_pda.0, &account.data)?;
+    Ok(config)
 }
 
 pub async fn get_miner(rpc: &RpcClient, authority: Pubkey) -> Result<Miner, anyhow::Error> {
     let miner_pda = ore_api::state::miner_pda(authority);
     let account = rpc.get_account(&miner_pda.0).await?;
-    let miner = Miner::try_from_bytes(&account.data)?;
-    Ok(*miner)
+    let miner = deserialize_account::<Miner>(&miner_pda.0, &account.data)?;
+    Ok(miner)
 }
 
+/// `None` if the wallet is not registered as a miner yet, the miner account
+/// is created by its first deploy.
+pub async fn get_miner_opt(rpc: &RpcClient, authority: Pubkey) -> Result<Option<Miner>, anyhow::Error> {
+    let miner_pda = ore_api::state::miner_pda(authority);
+    let account = rpc
+        .get_account_with_commitment(&miner_pda.0, rpc.commitment())
+        .await?
+        .value;
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    Ok(Some(deserialize_account::<Miner>(&miner_pda.0, &account.data)?))
+}
+
+/// Fetches the miner accounts of several authorities in one call, `None` for
+/// authorities that have no miner account.
+pub async fn get_miners(rpc: &RpcClient, authorities: &[Pubkey]) -> Result<Vec<Option<Miner>>, anyhow::Error> {
+    let addresses = authorities
+        .iter()
+        .map(|authority| ore_api::state::miner_pda(*authority).0)
+        .collect::<Vec<_>>();
+    let mut miners = vec![];
+    for chunk in addresses.chunks(100) {
+        for account in rpc.get_multiple_accounts(chunk).await? {
+            let miner = account.and_then(|account| Miner::try_from_bytes(&account.data).ok().copied());
+            miners.push(miner);
+        }
+    }
+    Ok(miners)
+}
+
+/// slot, epoch_start_timestamp, epoch, leader_schedule_epoch, unix_timestamp
+const CLOCK_SIZE: usize = 40;
+/// Retries of a rate-limited getProgramAccounts, waiting twice as long each time.
+const GPA_MAX_RETRIES: u32 = 5;
+const GPA_MIN_BACKOFF: Duration = Duration::from_millis(500);
+
 pub async fn get_clock(rpc: &RpcClient) -> Result<Clock, anyhow::Error> {
     let data = rpc.get_account_data(&solana_sdk::sysvar::clock::ID).await?;
-    let clock = bincode::deserialize::<Clock>(&data)?;
+    parse_clock(&data)
+}
+
+fn parse_clock(data: &[u8]) -> Result<Clock, anyhow::Error> {
+    if data.len() < CLOCK_SIZE {
+        anyhow::bail!("clock sysvar has {} bytes, expected {}", data.len(), CLOCK_SIZE);
+    }
+    let clock = bincode::deserialize::<Clock>(&data[..CLOCK_SIZE])?;
     Ok(clock)
 }
 
+/// Board, clock, the miners of the given wallets and round as of one slot.
+pub struct StateSnapshot {
+    pub board: Board,
+    pub clock: Clock,
+    /// One per authority asked for, `None` until that wallet is registered
+    /// as a miner.
+    pub miners: Vec<Option<Miner>>,
+    /// The round `round_id` asked for, which lags the board right after it
+    /// moves to a new round.
+    pub round: Option<Round>,
+}
+
+/// Reads board, clock, miner and round `round_id` in a single
+/// `getMultipleAccounts` call.
+pub async fn get_all_state(rpc: &RpcClient, authorities: &[Pubkey], round_id: u64) -> Result<StateSnapshot, anyhow::Error> {
+    let board_address = ore_api::state::board_pda().0;
+    let round_address = ore_api::state::round_pda(round_id).0;
+    let miner_addresses = authorities
+        .iter()
+        .map(|authority| ore_api::state::miner_pda(*authority).0)
+        .collect::<Vec<_>>();
+    let addresses = [board_address, solana_sdk::sysvar::clock::ID, round_address]
+        .into_iter()
+        .chain(miner_addresses.iter().copied())
+        .collect::<Vec<_>>();
+    let mut accounts = rpc
+        .get_multiple_accounts_with_commitment(&addresses, rpc.commitment())
+        .await?
+        .value;
+    if accounts.len() != addresses.len() {
+        anyhow::bail!("getMultipleAccounts returned the wrong number of accounts");
+    }
+    let miners = accounts.split_off(3);
+    let [board, clock, round]: [Option<_>; 3] = accounts
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("getMultipleAccounts returned the wrong number of accounts"))?;
+    let board = board.ok_or_else(|| anyhow::anyhow!("board account {} not found", board_address))?;
+    let clock = clock.ok_or_else(|| anyhow::anyhow!("clock sysvar not found"))?;
+    Ok(StateSnapshot {
+        board: deserialize_account::<Board>(&board_address, &board.data)?,
+        clock: parse_clock(&clock.data)?,
+        miners: miners
+            .into_iter()
+            .zip(&miner_addresses)
+            .map(|(miner, address)| miner.map(|miner| deserialize_account::<Miner>(address, &miner.data)).transpose())
+            .collect::<Result<_, _>>()?,
+        round: round
+            .map(|round| deserialize_account::<Round>(&round_address, &round.data))
+            .transpose()?,
+    })
+}
+
+/// Reads the clock sysvar with up to `retries` retries. If it still can't be
+/// read, returns `previous` with the slot from `getSlot`, the loop mostly
+/// needs the slot anyway.
+pub async fn get_clock_or_slot(rpc: &RpcClient, previous: &Clock, retries: u32) -> Result<Clock, anyhow::Error> {
+    let mut attempt = 0;
+    let err = loop {
+        match get_clock(rpc).await {
+            Ok(clock) => return Ok(clock),
+            Err(err) if attempt >= retries => break err,
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+    };
+    let slot = rpc.get_slot().await?;
+    warn!("failed to read the clock sysvar ({:?}), using slot {} from getSlot", err, slot);
+    Ok(Clock {
+        slot,
+        ..previous.clone()
+    })
+}
+
 pub async fn claim(
     rpc: &RpcClient,
     payer: &solana_sdk::signer::keypair::Keypair,
 ) -> Result<(), anyhow::Error> {
-    // let ix = ore_api::sdk::claim_ore(payer.pubkey(), u64::MAX);
+    // claim_ore no longer takes an amount, it always claims all unclaimed + refined ORE (see claim_all)
     let ix2 = ore_api::sdk::claim_sol(payer.pubkey());
-    submit_transaction_with_ixs(rpc, payer, &[ix2],DEFALUT_UNITS).await?;
+    submit_transaction_with_ixs(rpc, payer, &[ix2],DEFALUT_UNITS, PRIORITY_FEE, false, TxVersion::V0, None, &[]).await?;
     Ok(())
 }
 
+/// Claims all SOL and ORE rewards, returns whether a claim confirmed.
+pub async fn claim_all(
+    rpc: &RpcClient,
+    payer: &solana_sdk::signer::keypair::Keypair,
+    timeout: Duration,
+) -> Result<bool, anyhow::Error> {
+    let miner = get_miner(rpc, payer.pubkey()).await?;
+    if miner.rewards_sol == 0 && miner.rewards_ore == 0 && miner.refined_ore == 0 {
+        info!("Nothing to claim");
+        return Ok(false);
+    }
+
+    let ixs = [
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &ore_api::consts::MINT_ADDRESS,
+            &spl_token::ID,
+        ),
+        ore_api::sdk::claim_sol(payer.pubkey()),
+        ore_api::sdk::claim_ore(payer.pubkey()),
+    ];
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    let signature = rpc.send_transaction(&transaction).await?;
+    info!("Claim transaction sent: {}", signature);
+
+    match confirm_signature(rpc, &signature, timeout).await? {
+        Some(slot) => {
+            info!(
+                "Claimed sol: {} ore: {:.2} in slot {}",
+                lamports_to_sol(miner.rewards_sol),
+                amount_to_ui_amount(miner.rewards_ore + miner.refined_ore, TOKEN_DECIMALS),
+                slot
+            );
+            Ok(true)
+        }
+        None => {
+            warn!("Claim {} not confirmed within {:?}", signature, timeout);
+            Ok(false)
+        }
+    }
+}
+
+/// Polls the signature status until it is confirmed (returns the landing
+/// slot) or the timeout elapses (returns None).
+pub async fn confirm_signature(
+    rpc: &RpcClient,
+    signature: &Signature,
+    timeout: Duration,
+) -> Result<Option<u64>, anyhow::Error> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while tokio::time::Instant::now() < deadline {
+        let statuses = rpc.get_signature_statuses(&[*signature]).await?;
+        if let Some(Some(status)) = statuses.value.first() {
+            if let Some(err) = &status.err {
+                anyhow::bail!("Transaction {} failed: {:?}", signature, err);
+            }
+            if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                return Ok(Some(status.slot));
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    Ok(None)
+}
+
+/// How the copies of one deploy ended up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Landing {
+    /// The copy sent on this channel confirmed in this slot.
+    Landed(&'static str, u64),
+    /// Copies were processed but failed, usually AlreadyDeploy after an
+    /// earlier submission of the round landed.
+    Failed,
+    /// No copy was seen before the timeout.
+    Unseen,
+}
+
+/// Polls the signatures a deploy was sent under until one of them confirms,
+/// every one of them failed, or the timeout elapses. Only one copy of a
+/// deploy can land, so a failed copy doesn't end the poll while others pend.
+pub async fn confirm_first_landing(
+    rpc: &RpcClient,
+    candidates: &[(&'static str, Signature)],
+    timeout: Duration,
+) -> Result<Landing, anyhow::Error> {
+    let signatures = candidates.iter().map(|(_, signature)| *signature).collect::<Vec<_>>();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut failed = vec![false; candidates.len()];
+    while tokio::time::Instant::now() < deadline {
+        let statuses = rpc.get_signature_statuses(&signatures).await?;
+        for (index, status) in statuses.value.into_iter().enumerate() {
+            let Some(status) = status else {
+                continue;
+            };
+            if status.err.is_some() {
+                failed[index] = true;
+            } else if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                return Ok(Landing::Landed(candidates[index].0, status.slot));
+            }
+        }
+        if failed.iter().all(|failed| *failed) {
+            return Ok(Landing::Failed);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    Ok(if failed.contains(&true) { Landing::Failed } else { Landing::Unseen })
+}
+
+/// Custom error codes of the refined program, see idls/ore_por_program.json.
+pub const REFINED_NO_SQUARE_DEPLOY: u32 = 6002;
+
+pub fn decode_transaction_error(err: &TransactionError) -> String {
+    match err {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let name = match *code {
+                6000 => "InvalidSlot: the current slot does not meet the requirements",
+                6001 => "AlreadyDeploy: deployment has already been completed and cannot be repeated",
+                REFINED_NO_SQUARE_DEPLOY => "NoSquareDeploy: no qualifying square to deploy",
+                _ => "unknown program error",
+            };
+            format!("instruction {} failed with custom error {} ({})", index, code, name)
+        }
+        err => format!("{:?}", err),
+    }
+}
+
+/// Where a transaction goes, which decides its compute-budget defaults and
+/// whether it carries a jito tip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Rpc,
+    Jito { tip: u64 },
+    Simulate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub heap_frame: Option<u32>,
+    pub unit_limit: u32,
+    pub unit_price: u64,
+}
+
+impl Channel {
+    /// Default budget for `units` of compute, already sized by
+    /// `compute_units_for`: rpc pays the priority fee, jito relies on the tip,
+    /// simulation just needs room.
+    pub fn compute_budget(&self, units: u64) -> ComputeBudget {
+        match self {
+            Channel::Rpc => ComputeBudget {
+                heap_frame: None,
+                unit_limit: units as u32,
+                unit_price: PRIORITY_FEE,
+            },
+            Channel::Jito { .. } => ComputeBudget {
+                heap_frame: None,
+                unit_limit: units as u32,
+                unit_price: 0,
+            },
+            Channel::Simulate => ComputeBudget {
+                heap_frame: Some(256 * 1024),
+                unit_limit: 1_000_000,
+                unit_price: 10_000,
+            },
+        }
+    }
+}
+
+/// Full instruction list for a transaction: heap frame, compute unit limit
+/// and price, the jito tip if any, then the core instructions.
+pub fn assemble_instructions(
+    payer: &Pubkey,
+    channel: Channel,
+    budget: ComputeBudget,
+    instructions: &[solana_sdk::instruction::Instruction],
+) -> Vec<solana_sdk::instruction::Instruction> {
+    let mut all_instructions = vec![];
+    if let Some(bytes) = budget.heap_frame {
+        all_instructions.push(ComputeBudgetInstruction::request_heap_frame(bytes));
+    }
+    all_instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(budget.unit_limit));
+    all_instructions.push(ComputeBudgetInstruction::set_compute_unit_price(budget.unit_price));
+    if let Channel::Jito { tip } = channel {
+        all_instructions.push(jito::build_bribe_ix(payer, tip));
+    }
+    all_instructions.extend_from_slice(instructions);
+    all_instructions
+}
+
+/// Compute units to size a deploy for: the simulated units plus a 10% margin,
+/// kept within [min, max]. A `max` below `min` counts as `min`.
+pub fn compute_units_for(simulated: u64, min: u64, max: u64) -> u64 {
+    (simulated * 11 / 10).clamp(min, max.max(min))
+}
+
 pub async fn simulate_transaction(
     rpc: &RpcClient,
     payer: &solana_sdk::signer::keypair::Keypair,
     instructions: &[solana_sdk::instruction::Instruction],
 ) -> RpcResult<RpcSimulateTransactionResult> {
-    let mut all_instructions = vec![
-        ComputeBudgetInstruction::request_heap_frame(256 * 1024),
-        ComputeBudgetInstruction::set_compute_unit_limit(1000_000),
-        ComputeBudgetInstruction::set_compute_unit_price(10_000),
-    ];
-    all_instructions.extend_from_slice(instructions);
+    let all_instructions = assemble_instructions(
+        &payer.pubkey(),
+        Channel::Simulate,
+        Channel::Simulate.compute_budget(0),
+        instructions,
+    );
 
-    let blockhash = rpc.get_latest_blockhash().await.unwrap();
+    let blockhash = rpc.get_latest_blockhash().await?;
+    // also return the payer's miner account so callers can see where the
+    // refined program would deploy
+    let config = RpcSimulateTransactionConfig {
+        commitment: Some(rpc.commitment()),
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses: vec![miner_pda(payer.pubkey()).0.to_string()],
+        }),
+        ..Default::default()
+    };
     let x = rpc
-        .simulate_transaction(&Transaction::new_signed_with_payer(
+        .simulate_transaction_with_config(&Transaction::new_signed_with_payer(
             &all_instructions,
             Some(&payer.pubkey()),
             &[payer],
             blockhash,
-        ))
+        ), config)
         .await;
     info!("Simulation result: {:?}", x);
 
     return x
 }
 
+/// Per-square `deployed` of the payer's miner account after a successful
+/// `simulate_transaction`.
+pub fn simulated_miner_deployed(result: &RpcSimulateTransactionResult) -> Option<[u64; 25]> {
+    if result.err.is_some() {
+        return None;
+    }
+    let data = result
+        .accounts
+        .as_ref()
+        .and_then(|accounts| accounts.first().cloned().flatten())
+        .and_then(|account| account.data.decode())?;
+    Miner::try_from_bytes(&data).ok().map(|miner| miner.deployed)
+}
+
 
 pub async fn build_multi_sign_transaction(
     rpc: &RpcClient,
@@ -118,11 +514,12 @@ pub async fn build_multi_sign_transaction(
     address_lookup_table_accounts: &Vec<AddressLookupTableAccount>
 ) -> anyhow::Result<VersionedTransaction> {
     let blockhash = rpc.get_latest_blockhash().await?;
-    let mut all_instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_limit(700_000),
-        ComputeBudgetInstruction::set_compute_unit_price(500_000),
-    ];
-    all_instructions.extend_from_slice(instructions);
+    let budget = ComputeBudget {
+        heap_frame: None,
+        unit_limit: 700_000,
+        unit_price: 500_000,
+    };
+    let all_instructions = assemble_instructions(&payer.pubkey(), Channel::Rpc, budget, instructions);
 
 
     info!("Building transaction with {} signers", pairs.len()+1);
@@ -132,72 +529,154 @@ pub async fn build_multi_sign_transaction(
             &payer.pubkey(),
             &all_instructions,
             address_lookup_table_accounts,
-            blockhash.clone(),
-        ).unwrap()),
+            blockhash,
+        )?),
         pairs,
-    ).unwrap();
+    )?;
+
 
+    Ok(transaction)
+}
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxVersion {
+    V0,
+    Legacy,
+}
 
+/// `lookup_tables` only apply to v0 transactions.
+pub fn build_versioned_transaction(
+    payer: &solana_sdk::signer::keypair::Keypair,
+    instructions: &[solana_sdk::instruction::Instruction],
+    blockhash: solana_sdk::hash::Hash,
+    version: TxVersion,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> anyhow::Result<VersionedTransaction> {
+    let transaction = match version {
+        TxVersion::Legacy => VersionedTransaction::from(Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        )),
+        TxVersion::V0 => VersionedTransaction::try_new(
+            VersionedMessage::V0(v0::Message::try_compile(
+                &payer.pubkey(),
+                instructions,
+                lookup_tables,
+                blockhash,
+            )?),
+            &[payer],
+        )?,
+    };
     Ok(transaction)
 }
+
 pub async fn submit_transaction_with_ixs(
     rpc: &RpcClient,
     payer: &solana_sdk::signer::keypair::Keypair,
     instructions: &[solana_sdk::instruction::Instruction],
     units: u64,
-) -> Result<(), anyhow::Error> {
-    let blockhash = rpc.get_latest_blockhash().await?;
-    let mut all_instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_limit((units * 11 / 10)as u32 ),
-        ComputeBudgetInstruction::set_compute_unit_price(20000),
-    ];
-    all_instructions.extend_from_slice(instructions);
-    let transaction = Transaction::new_signed_with_payer(
-        &all_instructions,
-        Some(&payer.pubkey()),
-        &[payer],
-        blockhash,
+    unit_price: u64,
+    skip_preflight: bool,
+    version: TxVersion,
+    blockhash: Option<Hash>,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Signature, anyhow::Error> {
+    let blockhash = match blockhash {
+        Some(blockhash) => blockhash,
+        None => rpc.get_latest_blockhash().await?,
+    };
+    let all_instructions = assemble_instructions(
+        &payer.pubkey(),
+        Channel::Rpc,
+        ComputeBudget {
+            unit_price,
+            ..Channel::Rpc.compute_budget(units)
+        },
+        instructions,
     );
+    let transaction = build_versioned_transaction(payer, &all_instructions, blockhash, version, lookup_tables)?;
 
+    // the hot path simulates at processed commitment right before sending, so
+    // preflight (when enabled) checks against the same state
     let config = solana_client::rpc_config::RpcSendTransactionConfig {
-        skip_preflight: true,
+        skip_preflight,
+        preflight_commitment: Some(CommitmentLevel::Processed),
         ..Default::default()
     };
     rpc.send_transaction_with_config(&transaction,config).await?;
     info!("Transaction sent: {}", transaction.signatures[0]);
-    Ok(())
+    Ok(transaction.signatures[0])
 }
 
-pub async fn send_ix_use_jito(
+pub async fn build_jito_transaction(
     rpc: &RpcClient,
     payer: &solana_sdk::signer::keypair::Keypair,
     instructions: &[solana_sdk::instruction::Instruction],
     units: u64,
-) -> anyhow::Result<()> {
-
-    let blockhash = rpc.get_latest_blockhash().await?;
-    let mut all_instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_limit(units as u32),
-        ComputeBudgetInstruction::set_compute_unit_price(0),
-    ];
-    all_instructions.extend_from_slice(instructions);
-
-    let jito_ixs = [jito::build_bribe_ix(&payer.pubkey(), 5000)];
+    tip: u64,
+    blockhash: Option<Hash>,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> anyhow::Result<VersionedTransaction> {
+    let blockhash = match blockhash {
+        Some(blockhash) => blockhash,
+        None => rpc.get_latest_blockhash().await?,
+    };
+    let channel = Channel::Jito { tip };
+    let all_instructions = assemble_instructions(
+        &payer.pubkey(),
+        channel,
+        channel.compute_budget(units),
+        instructions,
+    );
     let transaction = VersionedTransaction::try_new(
         VersionedMessage::V0(v0::Message::try_compile(
             &payer.pubkey(),
-            &vec![jito_ixs.as_slice(), &all_instructions].concat(),
-            &vec![],
-            blockhash.clone(),
-        ).unwrap()),
+            &all_instructions,
+            lookup_tables,
+            blockhash,
+        )?),
         &[&payer],
-    ).unwrap();
+    )?;
+
+    Ok(transaction)
+}
 
+/// Sends the bundle and returns the transaction signature plus the bundle id
+/// if the block engine accepted it.
+pub async fn send_ix_use_jito(
+    rpc: &RpcClient,
+    payer: &solana_sdk::signer::keypair::Keypair,
+    instructions: &[solana_sdk::instruction::Instruction],
+    units: u64,
+    tip: u64,
+    blockhash: Option<Hash>,
+    regions: Option<&JitoRegionSelector>,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> anyhow::Result<(Signature, BundleOutcome)> {
+    let transaction = build_jito_transaction(rpc, payer, instructions, units, tip, blockhash, lookup_tables).await?;
+    let signature = transaction.signatures[0];
 
-    send_bundle(vec![transaction]).await?;
+    let outcome = send_bundle(vec![transaction], regions).await?;
 
-    Ok(())
+    Ok((signature, outcome))
+}
 
+/// Sends an already signed transaction (e.g. the jito one) through the rpc,
+/// so both channels carry the same signature.
+pub async fn send_versioned_transaction(
+    rpc: &RpcClient,
+    transaction: &VersionedTransaction,
+    skip_preflight: bool,
+) -> Result<Signature, anyhow::Error> {
+    let config = solana_client::rpc_config::RpcSendTransactionConfig {
+        skip_preflight,
+        preflight_commitment: Some(CommitmentLevel::Processed),
+        ..Default::default()
+    };
+    let signature = rpc.send_transaction_with_config(transaction, config).await?;
+    info!("Transaction sent: {}", signature);
+    Ok(signature)
 }
 
 
@@ -214,49 +693,46 @@ where
         &T::discriminator().to_le_bytes(),
     ))];
     all_filters.extend(filters);
-    let result = client
-        .get_program_accounts_with_config(
-            &program_id,
-            RpcProgramAccountsConfig {
-                filters: Some(all_filters),
-                account_config: RpcAccountInfoConfig {
-                    encoding: Some(UiAccountEncoding::Base64),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-        )
-        .await;
+    let config = RpcProgramAccountsConfig {
+        filters: Some(all_filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
 
-    match result {
-        Ok(accounts) => {
-            let accounts = accounts
-                .into_iter()
-                .filter_map(|(pubkey, account)| {
-                    if let Ok(account) = T::try_from_bytes(&account.data) {
-                        Some((pubkey, account.clone()))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            Ok(accounts)
-        }
-        Err(err) => match err.kind {
-            ClientErrorKind::Reqwest(err) => {
-                if let Some(status_code) = err.status() {
-                    if status_code == StatusCode::GONE {
-                        panic!(
-                            "\n{} Your RPC provider does not support the getProgramAccounts endpoint, needed to execute this command. Please use a different RPC provider.\n",
-                            "ERROR"
-                        );
-                    }
-                }
-                return Err(anyhow::anyhow!("Failed to get program accounts: {}", err));
+    let mut backoff = GPA_MIN_BACKOFF;
+    let mut retries = 0;
+    let accounts = loop {
+        let err = match client.get_program_accounts_with_config(&program_id, config.clone()).await {
+            Ok(accounts) => break accounts,
+            Err(err) => err,
+        };
+        let status = match &err.kind {
+            ClientErrorKind::Reqwest(err) => err.status(),
+            _ => None,
+        };
+        match status {
+            Some(StatusCode::GONE) => return Err(OreError::ProgramAccountsUnsupported.into()),
+            Some(StatusCode::TOO_MANY_REQUESTS) if retries < GPA_MAX_RETRIES => {
+                retries += 1;
+                warn!("getProgramAccounts rate limited, retry {} of {} in {:?}", retries, GPA_MAX_RETRIES, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
             }
             _ => return Err(anyhow::anyhow!("Failed to get program accounts: {}", err)),
-        },
-    }
+        }
+    };
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(pubkey, account)| {
+            T::try_from_bytes(&account.data)
+                .ok()
+                .map(|account| (pubkey, account.clone()))
+        })
+        .collect())
 }
 
 
@@ -291,4 +767,252 @@ pub async fn log_treasury(rpc: &RpcClient) -> Result<(), anyhow::Error> {
         amount_to_ui_amount(treasury.total_refined, TOKEN_DECIMALS)
     );
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Latest blockhash together with the slot it was fetched at, so the submit
+/// path never signs with one older than `max_age_slots`.
+pub struct BlockhashCache {
+    latest: tokio::sync::Mutex<Option<(Hash, u64)>>,
+    max_age_slots: u64,
+}
+
+impl BlockhashCache {
+    pub fn new(max_age_slots: u64) -> Self {
+        Self {
+            latest: tokio::sync::Mutex::new(None),
+            max_age_slots,
+        }
+    }
+
+    pub async fn refresh(&self, rpc: &RpcClient, slot: u64) -> Result<(Hash, u64), anyhow::Error> {
+        let blockhash = rpc.get_latest_blockhash().await?;
+        *self.latest.lock().await = Some((blockhash, slot));
+        Ok((blockhash, slot))
+    }
+
+    /// The cached blockhash and the slot it was fetched at, refreshed first
+    /// if it was fetched more than `max_age_slots` before `slot`.
+    pub async fn get(&self, rpc: &RpcClient, slot: u64) -> Result<(Hash, u64), anyhow::Error> {
+        if let Some((blockhash, fetched_slot)) = *self.latest.lock().await {
+            let age = slot.saturating_sub(fetched_slot);
+            if age <= self.max_age_slots {
+                return Ok((blockhash, fetched_slot));
+            }
+            warn!("cached blockhash is {} slots old (max {}), refreshing", age, self.max_age_slots);
+        }
+        self.refresh(rpc, slot).await
+    }
+}
+
+/// Time budget shared by every rpc call made while deploying in one round,
+/// so retries in one step can't eat the time the next step needs.
+pub struct RoundBudget {
+    pub round_id: u64,
+    deadline: tokio::time::Instant,
+}
+
+impl RoundBudget {
+    pub fn new(round_id: u64, budget: Duration) -> Self {
+        Self {
+            round_id,
+            deadline: tokio::time::Instant::now() + budget,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(tokio::time::Instant::now())
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    pub async fn spend<T, E>(
+        &self,
+        fut: impl std::future::Future<Output = Result<T, E>>,
+    ) -> Result<T, anyhow::Error>
+    where
+        E: Into<anyhow::Error>,
+    {
+        match tokio::time::timeout_at(self.deadline, fut).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => anyhow::bail!("round {} budget exhausted", self.round_id),
+        }
+    }
+}
+
+/// Simulates the instructions and returns the per-square `deployed` amounts of
+/// the payer's miner account after the simulated transaction.
+pub async fn simulate_deployed_squares(
+    rpc: &RpcClient,
+    payer: &solana_sdk::signer::keypair::Keypair,
+    instructions: &[solana_sdk::instruction::Instruction],
+) -> Result<Option<[u64; 25]>, anyhow::Error> {
+    let result = simulate_transaction(rpc, payer, instructions).await?;
+    Ok(simulated_miner_deployed(&result.value))
+}
+
+/// Aborts the background tasks it holds when dropped, so the update loops of
+/// a finished mining session don't outlive it.
+pub struct AbortOnDrop(pub Vec<JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn build_versioned_transaction_in_both_versions() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let ixs = [system_instruction::transfer(&payer.pubkey(), &recipient, 1_000)];
+        let blockhash = Hash::new_unique();
+
+        let legacy = build_versioned_transaction(&payer, &ixs, blockhash, TxVersion::Legacy, &[]).unwrap();
+        assert!(matches!(legacy.message, VersionedMessage::Legacy(_)));
+        let v0 = build_versioned_transaction(&payer, &ixs, blockhash, TxVersion::V0, &[]).unwrap();
+        assert!(matches!(v0.message, VersionedMessage::V0(_)));
+        for transaction in [&legacy, &v0] {
+            assert_eq!(transaction.message.recent_blockhash(), &blockhash);
+            assert_eq!(transaction.message.static_account_keys()[0], payer.pubkey());
+            assert_eq!(transaction.message.instructions().len(), 1);
+            assert!(transaction.verify_with_results().iter().all(|ok| *ok));
+        }
+    }
+
+    #[test]
+    fn v0_transaction_resolves_accounts_through_lookup_tables() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let ixs = [system_instruction::transfer(&payer.pubkey(), &recipient, 1_000)];
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![recipient],
+        };
+        let v0 = build_versioned_transaction(&payer, &ixs, Hash::new_unique(), TxVersion::V0, &[table]).unwrap();
+        assert!(!v0.message.static_account_keys().contains(&recipient));
+        assert_eq!(v0.message.address_table_lookups().map(|lookups| lookups.len()), Some(1));
+    }
+
+    fn core_ixs(payer: &Pubkey) -> Vec<Instruction> {
+        vec![
+            system_instruction::transfer(payer, &Pubkey::new_unique(), 1),
+            system_instruction::transfer(payer, &Pubkey::new_unique(), 2),
+        ]
+    }
+
+    #[test]
+    fn assemble_rpc_puts_the_budget_before_the_core_instructions() {
+        let payer = Pubkey::new_unique();
+        let core = core_ixs(&payer);
+        let budget = Channel::Rpc.compute_budget(100_000);
+        assert_eq!(budget, ComputeBudget { heap_frame: None, unit_limit: 100_000, unit_price: PRIORITY_FEE });
+        let ixs = assemble_instructions(&payer, Channel::Rpc, budget, &core);
+        assert_eq!(
+            ixs,
+            [
+                vec![
+                    ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                    ComputeBudgetInstruction::set_compute_unit_price(PRIORITY_FEE),
+                ],
+                core,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn assemble_jito_puts_the_tip_right_before_the_core_instructions() {
+        let payer = Pubkey::new_unique();
+        let core = core_ixs(&payer);
+        let channel = Channel::Jito { tip: 42_000 };
+        let ixs = assemble_instructions(&payer, channel, channel.compute_budget(100_000), &core);
+        assert_eq!(ixs.len(), 5);
+        assert_eq!(ixs[0], ComputeBudgetInstruction::set_compute_unit_limit(100_000));
+        assert_eq!(ixs[1], ComputeBudgetInstruction::set_compute_unit_price(0));
+        // the tip account is picked at random, the rest of the transfer is fixed
+        let tip = &ixs[2];
+        assert_eq!(tip.accounts[0].pubkey, payer);
+        assert_eq!(*tip, system_instruction::transfer(&payer, &tip.accounts[1].pubkey, 42_000));
+        assert_eq!(ixs[3..], core[..]);
+    }
+
+    #[test]
+    fn assemble_simulate_requests_a_heap_frame_first() {
+        let payer = Pubkey::new_unique();
+        let core = core_ixs(&payer);
+        let ixs = assemble_instructions(&payer, Channel::Simulate, Channel::Simulate.compute_budget(100_000), &core);
+        assert_eq!(
+            ixs,
+            [
+                vec![
+                    ComputeBudgetInstruction::request_heap_frame(256 * 1024),
+                    ComputeBudgetInstruction::set_compute_unit_limit(1_000_000),
+                    ComputeBudgetInstruction::set_compute_unit_price(10_000),
+                ],
+                core,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn compute_units_add_a_margin_within_bounds() {
+        assert_eq!(compute_units_for(300_000, 200_000, 600_000), 330_000);
+        // a cheap or failed simulation still gets the floor
+        assert_eq!(compute_units_for(0, 200_000, 600_000), 200_000);
+        assert_eq!(compute_units_for(150_000, 200_000, 600_000), 200_000);
+        // an outlier is capped
+        assert_eq!(compute_units_for(1_400_000, 200_000, 600_000), 600_000);
+        assert_eq!(compute_units_for(545_455, 200_000, 600_000), 600_000);
+        assert_eq!(compute_units_for(545_454, 200_000, 600_000), 599_999);
+        // a ceiling below the floor never panics in clamp
+        assert_eq!(compute_units_for(300_000, 200_000, 100_000), 200_000);
+    }
+
+    #[test]
+    fn rpc_limit_never_exceeds_the_ceiling() {
+        let units = compute_units_for(1_400_000, 200_000, 600_000);
+        assert_eq!(Channel::Rpc.compute_budget(units).unit_limit, 600_000);
+        assert_eq!(Channel::Jito { tip: 1 }.compute_budget(units).unit_limit, 600_000);
+    }
+
+    /// Nothing listens there, any call that reaches the rpc fails fast.
+    fn unreachable_rpc() -> RpcClient {
+        RpcClient::new_with_timeout("http://127.0.0.1:1".to_string(), Duration::from_secs(1))
+    }
+
+    #[tokio::test]
+    async fn blockhash_cache_serves_a_fresh_blockhash_without_the_rpc() {
+        let cache = BlockhashCache::new(30);
+        let blockhash = Hash::new_unique();
+        *cache.latest.lock().await = Some((blockhash, 1_000));
+        let rpc = unreachable_rpc();
+        assert_eq!(cache.get(&rpc, 1_000).await.unwrap(), (blockhash, 1_000));
+        assert_eq!(cache.get(&rpc, 1_030).await.unwrap(), (blockhash, 1_000));
+    }
+
+    #[tokio::test]
+    async fn blockhash_cache_refreshes_an_aged_blockhash() {
+        let cache = BlockhashCache::new(30);
+        let blockhash = Hash::new_unique();
+        *cache.latest.lock().await = Some((blockhash, 1_000));
+        // one slot past the max age goes to the rpc, which is down here
+        assert!(cache.get(&unreachable_rpc(), 1_031).await.is_err());
+        assert_eq!(*cache.latest.lock().await, Some((blockhash, 1_000)));
+    }
+
+    #[tokio::test]
+    async fn empty_blockhash_cache_goes_to_the_rpc() {
+        let cache = BlockhashCache::new(30);
+        assert!(cache.get(&unreachable_rpc(), 0).await.is_err());
+    }
+}
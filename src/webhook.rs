@@ -0,0 +1,115 @@
+use serde::Serialize;
+use solana_client::client_error::reqwest;
+
+/// Bumped on any breaking change of the `DeployConfirmed` payload.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeploySquare {
+    pub index: usize,
+    pub amount: u64,
+}
+
+/// Inputs the deploy was decided on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub slot: u64,
+    pub slot_left: u64,
+    pub ore_price: f64,
+    pub sol_price: f64,
+    pub ore_refined_rate: f64,
+    pub remaining_slots: u8,
+    pub pot: u64,
+}
+
+/// Complete record of one landed deploy, posted once it is confirmed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeployConfirmed {
+    pub schema_version: u32,
+    pub round_id: u64,
+    pub channel: &'static str,
+    pub signature: String,
+    pub bundle_id: Option<String>,
+    pub landed_slot: u64,
+    pub amount: u64,
+    /// Squares from simulating the deploy, empty if the simulation returned no miner account.
+    pub squares: Vec<DeploySquare>,
+    pub program_fee: u64,
+    pub priority_fee: u64,
+    pub jito_tip: u64,
+    pub decision: Decision,
+}
+
+impl DeployConfirmed {
+    pub fn squares_from(deployed: &[u64; 25]) -> Vec<DeploySquare> {
+        (0..25)
+            .filter(|i| deployed[*i] > 0)
+            .map(|index| DeploySquare { index, amount: deployed[index] })
+            .collect()
+    }
+}
+
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn post(&self, event: &DeployConfirmed) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(event)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_field_names_are_stable() {
+        let mut deployed = [0; 25];
+        deployed[3] = 10;
+        let event = DeployConfirmed {
+            schema_version: SCHEMA_VERSION,
+            round_id: 1,
+            channel: "jito",
+            signature: "sig".to_string(),
+            bundle_id: None,
+            landed_slot: 100,
+            amount: 10,
+            squares: DeployConfirmed::squares_from(&deployed),
+            program_fee: 0,
+            priority_fee: 5,
+            jito_tip: 1_000,
+            decision: Decision {
+                slot: 99,
+                slot_left: 3,
+                ore_price: 2.0,
+                sol_price: 150.0,
+                ore_refined_rate: 0.5,
+                remaining_slots: 4,
+                pot: 1_000_000,
+            },
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["channel"], "jito");
+        assert!(value["bundle_id"].is_null());
+        assert_eq!(value["squares"], serde_json::json!([{"index": 3, "amount": 10}]));
+        assert_eq!(value["jito_tip"], 1_000);
+        assert_eq!(value["decision"]["remaining_slots"], 4);
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const LATENCY_EMA_ALPHA: f64 = 0.3;
+/// Assumed submit-to-land latency until a deploy has been measured.
+const DEFAULT_LATENCY_SLOTS: f64 = 2.0;
+const SLOT_TIME_EMA_ALPHA: f64 = 0.2;
+
+/// Moving average of the slots between submitting a deploy and it landing.
+#[derive(Debug, Default)]
+pub struct LandingLatency {
+    ema: Option<f64>,
+}
+
+impl LandingLatency {
+    pub fn observe(&mut self, submitted_slot: u64, landed_slot: u64) {
+        let slots = landed_slot.saturating_sub(submitted_slot) as f64;
+        self.ema = Some(match self.ema {
+            Some(ema) => ema + LATENCY_EMA_ALPHA * (slots - ema),
+            None => slots,
+        });
+        info!("deploy landed {} slots after submit, landing latency {:.2} slots", slots, self.slots());
+    }
+
+    pub fn ema(&self) -> Option<f64> {
+        self.ema
+    }
+
+    pub fn restore(&mut self, ema: Option<f64>) {
+        self.ema = ema;
+    }
+
+    pub fn slots(&self) -> f64 {
+        self.ema.unwrap_or(DEFAULT_LATENCY_SLOTS)
+    }
+}
+
+/// Last slot a deploy can be submitted in and still be likely to land before
+/// the round ends. The start of the window is the `remaining_slots` check the
+/// loop does anyway.
+#[derive(Debug, Clone, Copy)]
+pub struct CommitWindow {
+    pub latest_safe_slot: u64,
+}
+
+impl CommitWindow {
+    pub fn new(end_slot: u64, latency_slots: f64, margin_slots: u64) -> Self {
+        Self {
+            latest_safe_slot: end_slot.saturating_sub(latency_slots.ceil() as u64 + margin_slots),
+        }
+    }
+
+    pub fn too_late(&self, slot: u64) -> bool {
+        slot > self.latest_safe_slot
+    }
+}
+
+/// Moving average of the wall-clock time per slot, from how fast the observed
+/// slot advances. The configured default is used until `warmup` samples are in.
+#[derive(Debug)]
+pub struct SlotTime {
+    default_ms: f64,
+    warmup: u64,
+    samples: u64,
+    ema_ms: Option<f64>,
+    last: Option<(u64, Instant)>,
+}
+
+impl SlotTime {
+    pub fn new(default_ms: u64, warmup: u64) -> Self {
+        Self {
+            default_ms: default_ms as f64,
+            warmup,
+            samples: 0,
+            ema_ms: None,
+            last: None,
+        }
+    }
+
+    pub fn observe(&mut self, slot: u64, at: Instant) {
+        let Some((last_slot, last_at)) = self.last else {
+            self.last = Some((slot, at));
+            return;
+        };
+        if slot <= last_slot {
+            return;
+        }
+        let ms = at.duration_since(last_at).as_secs_f64() * 1000.0 / (slot - last_slot) as f64;
+        self.ema_ms = Some(match self.ema_ms {
+            Some(ema) => ema + SLOT_TIME_EMA_ALPHA * (ms - ema),
+            None => ms,
+        });
+        self.samples += 1;
+        self.last = Some((slot, at));
+    }
+
+    pub fn ms(&self) -> f64 {
+        match self.ema_ms {
+            Some(ema) if self.samples >= self.warmup => ema,
+            _ => self.default_ms,
+        }
+    }
+
+    pub fn time_for(&self, slots: u64) -> Duration {
+        Duration::from_secs_f64(slots as f64 * self.ms() / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_window_leaves_room_for_latency_and_margin() {
+        let window = CommitWindow::new(1_000, 2.3, 1);
+        assert_eq!(window.latest_safe_slot, 996);
+        assert!(!window.too_late(996));
+        assert!(window.too_late(997));
+        assert_eq!(CommitWindow::new(2, 2.0, 1).latest_safe_slot, 0);
+    }
+
+    #[test]
+    fn landing_latency_starts_from_the_default_and_averages() {
+        let mut latency = LandingLatency::default();
+        assert_eq!(latency.slots(), DEFAULT_LATENCY_SLOTS);
+        latency.observe(100, 104);
+        assert_eq!(latency.slots(), 4.0);
+        latency.observe(200, 201);
+        assert!((latency.slots() - (4.0 + LATENCY_EMA_ALPHA * (1.0 - 4.0))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn slot_time_uses_the_default_until_warmed_up() {
+        let start = Instant::now();
+        let mut slot_time = SlotTime::new(400, 2);
+        slot_time.observe(10, start);
+        slot_time.observe(12, start + Duration::from_millis(1_000));
+        assert_eq!(slot_time.ms(), 400.0);
+        // a slot that didn't move is no sample
+        slot_time.observe(12, start + Duration::from_millis(1_200));
+        slot_time.observe(14, start + Duration::from_millis(2_000));
+        assert!((slot_time.ms() - 500.0).abs() < 1e-9);
+        assert_eq!(slot_time.time_for(4), Duration::from_secs(2));
+    }
+}